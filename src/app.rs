@@ -4,6 +4,8 @@ use std::io;
 
 use env_logger::Env;
 
+use crate::input::Input;
+use crate::options::LessOptions;
 use crate::renderloop;
 
 pub struct MiniLessApp {
@@ -25,7 +27,7 @@ impl MiniLessApp {
         }
     }
 
-    pub fn run(self, filename: &str) -> io::Result<()> {
-        renderloop::less_loop(filename)
+    pub fn run(self, filename: &str, input: Input, options: &LessOptions) -> io::Result<()> {
+        renderloop::less_loop(filename, input, options)
     }
 }
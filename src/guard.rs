@@ -0,0 +1,81 @@
+use std::io;
+use std::io::stdout;
+use std::panic;
+use std::process;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+
+use crossterm::{
+    cursor::{EnableBlinking, MoveTo, Show},
+    execute,
+    style::ResetColor,
+    terminal,
+    terminal::{disable_raw_mode, enable_raw_mode, Clear, ClearType, EnterAlternateScreen, LeaveAlternateScreen},
+};
+use signal_hook::consts::signal::{SIGHUP, SIGINT, SIGQUIT, SIGTERM};
+use signal_hook::iterator::Signals;
+
+// NOTE: use from panic hook and signal handler thread, so it is global
+static ALTERNATE_SCREEN: AtomicBool = AtomicBool::new(false);
+// the terminal is restored once, e.g. the panic hook and then the drop by unwinding
+static RESTORED: AtomicBool = AtomicBool::new(false);
+
+// restore the terminal state when dropped, also on panic and on signal
+pub struct TerminalGuard;
+
+impl TerminalGuard {
+    pub fn new(alternate_screen: bool) -> io::Result<TerminalGuard> {
+        ALTERNATE_SCREEN.store(alternate_screen, Ordering::SeqCst);
+        RESTORED.store(false, Ordering::SeqCst);
+        install_panic_hook();
+        install_signal_handler()?;
+        enable_raw_mode()?;
+        if alternate_screen {
+            execute!(stdout(), EnterAlternateScreen)?;
+        }
+        Ok(TerminalGuard)
+    }
+}
+
+impl Drop for TerminalGuard {
+    fn drop(&mut self) {
+        restore();
+    }
+}
+
+pub fn restore() {
+    // NOTE: leaving the alternate screen twice restores the cursor before the launch,
+    // and the shell prompt overwrites the panic message
+    if RESTORED.swap(true, Ordering::SeqCst) {
+        return;
+    }
+    let mut stdout = stdout();
+    let _ = disable_raw_mode();
+    let _ = execute!(stdout, ResetColor, Show, EnableBlinking);
+    if ALTERNATE_SCREEN.load(Ordering::SeqCst) {
+        let _ = execute!(stdout, LeaveAlternateScreen);
+    } else if let Ok((_, window_rows)) = terminal::size() {
+        // put the shell prompt on the bottom line, same as less
+        let _ = execute!(stdout, MoveTo(0, window_rows.saturating_sub(1)), Clear(ClearType::CurrentLine));
+    }
+}
+
+fn install_panic_hook() {
+    let default_hook = panic::take_hook();
+    panic::set_hook(Box::new(move |info| {
+        // restore before printing the panic message
+        restore();
+        default_hook(info);
+    }));
+}
+
+fn install_signal_handler() -> io::Result<()> {
+    let mut signals = Signals::new([SIGHUP, SIGINT, SIGQUIT, SIGTERM])?;
+    thread::spawn(move || {
+        if let Some(signal) = signals.forever().next() {
+            restore();
+            process::exit(128 + signal);
+        }
+    });
+    Ok(())
+}
@@ -0,0 +1,397 @@
+use log::debug;
+use std::fs;
+use std::fs::File;
+use std::io;
+use std::io::IsTerminal;
+use std::io::Read;
+use std::io::{Seek, SeekFrom};
+use std::os::unix::fs::MetadataExt;
+use std::time::SystemTime;
+
+use crate::options::LessOptions;
+use crate::preprocess;
+use crate::utils;
+use crate::utils::ansi::LineStyles;
+use crate::utils::compress;
+use crate::utils::compress::Compression;
+use crate::utils::hexdump;
+
+// filename used for standard input, same as less
+pub const STDIN_FILENAME: &str = "-";
+
+pub fn is_stdin(filename: &str) -> bool {
+    filename == STDIN_FILENAME
+}
+
+pub fn is_missing_input(filename: &str) -> bool {
+    is_stdin(filename) && io::stdin().is_terminal()
+}
+
+// read all bytes of the input
+pub fn read(filename: &str) -> io::Result<Vec<u8>> {
+    let mut bytes = vec![];
+    if is_stdin(filename) {
+        // NOTE: keyboard input is read from /dev/tty by crossterm when stdin is not a terminal
+        io::stdin().lock().read_to_end(&mut bytes)?;
+    } else {
+        File::open(filename)?.read_to_end(&mut bytes)?;
+    }
+    Ok(bytes)
+}
+
+// loaded content of the input
+pub struct Input {
+    pub lines: ropey::Rope,
+    // hex dump view of the binary content
+    pub is_hex_view: bool,
+    // compression format of the input, the lines are decompressed
+    pub compression: Option<Compression>,
+    // the lines are the output of the preprocessor, not the content of the file
+    pub is_preprocessed: bool,
+    // bytes of the content, use with follow mode
+    pub size: u64,
+    // bytes of the last line which is not complete, use with follow mode
+    pub tail: Vec<u8>,
+    // version of the file when it is loaded, use with --watch
+    pub version: Option<FileVersion>,
+}
+
+// version of the file on disk, it is changed by writing or replacing the file
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct FileVersion {
+    modified: Option<SystemTime>,
+    size: u64,
+    ino: u64,
+}
+
+// version of the file, None for stdin and the missing file
+pub fn get_file_version(filename: &str) -> Option<FileVersion> {
+    if is_stdin(filename) {
+        return None;
+    }
+    let metadata = fs::metadata(filename).ok()?;
+    Some(FileVersion {
+        modified: metadata.modified().ok(),
+        size: metadata.len(),
+        ino: metadata.ino(),
+    })
+}
+
+// bytes of the last line which is not complete, the line is replaced when the bytes are appended.
+// the line of the hex dump view is 16 bytes
+fn get_tail(bytes: &[u8], is_hex_view: bool) -> &[u8] {
+    let start = if is_hex_view {
+        bytes.len() - bytes.len() % hexdump::LINE_BYTES
+    } else {
+        bytes.iter().rposition(|b| *b == b'\n').map_or(0, |idx| idx + 1)
+    };
+    &bytes[start..]
+}
+
+// text of the bytes from the byte offset in the content
+fn get_text(bytes: &[u8], offset: u64, is_hex_view: bool) -> String {
+    if is_hex_view {
+        hexdump::dump(bytes, offset as usize)
+    } else {
+        utils::bytes::decode_lossy(bytes)
+    }
+}
+
+// load the input into the rope through the preprocessor, the compressed input is decompressed and the binary content is the hex dump.
+// the invalid UTF-8 bytes of the text are kept by utils::bytes::decode_lossy
+pub fn load(filename: &str, options: &LessOptions) -> io::Result<Input> {
+    // NOTE: get the version before reading, not to miss the change while reading
+    let version = get_file_version(filename);
+    // NOTE: the preprocessor is not for stdin
+    let preprocessed = if options.no_lessopen || is_stdin(filename) {
+        None
+    } else {
+        preprocess::preprocess(filename)
+    };
+    let is_preprocessed = preprocessed.is_some();
+    let mut bytes = match preprocessed {
+        Some(bytes) => bytes,
+        None => read(filename)?,
+    };
+    let mut compression = compress::detect(&bytes);
+    if let Some(c) = compression {
+        // NOTE: show the raw bytes when the decompress is failed, e.g. the text starting with the magic number
+        match compress::decompress(&bytes, c) {
+            Ok(decompressed) => bytes = decompressed,
+            Err(e) => {
+                debug!("decompress error: {}: {:?}", c.name(), e);
+                compression = None;
+            }
+        }
+    }
+    let is_hex_view = options.hex_dump || (!options.text && hexdump::is_binary(&bytes));
+    Ok(Input {
+        lines: ropey::Rope::from_str(&get_text(&bytes, 0, is_hex_view)),
+        is_hex_view,
+        compression,
+        is_preprocessed,
+        size: bytes.len() as u64,
+        tail: get_tail(&bytes, is_hex_view).to_vec(),
+        version,
+    })
+}
+
+// reader of the bytes appended to the growing file, use with follow mode
+pub struct Follower {
+    filename: String,
+    file: File,
+    // byte offset of the tail in the content
+    tail_offset: u64,
+    tail: Vec<u8>,
+    is_hex_view: bool,
+}
+
+impl Follower {
+    pub fn new(filename: &str, input: &Input) -> io::Result<Follower> {
+        // NOTE: stdin is read until EOF, and the decompressed or preprocessed content can't be appended
+        if is_stdin(filename) || input.compression.is_some() || input.is_preprocessed {
+            return Err(io::Error::new(io::ErrorKind::Unsupported, "can't follow this input"));
+        }
+        let mut file = File::open(filename)?;
+        file.seek(SeekFrom::Start(input.size))?;
+        Ok(Follower {
+            filename: filename.to_string(),
+            file,
+            tail_offset: input.size - input.tail.len() as u64,
+            tail: input.tail.clone(),
+            is_hex_view: input.is_hex_view,
+        })
+    }
+
+    // the file is truncated, or the other file is created with the same name by the rotation (e.g. logrotate)
+    pub fn is_replaced(&self) -> bool {
+        let Ok(metadata) = self.file.metadata() else {
+            return false;
+        };
+        let is_truncated = metadata.len() < self.tail_offset + self.tail.len() as u64;
+        // NOTE: keep following the rotated file until the new file is created
+        let is_rotated =
+            fs::metadata(&self.filename).is_ok_and(|now| (now.dev(), now.ino()) != (metadata.dev(), metadata.ino()));
+        is_truncated || is_rotated
+    }
+
+    // read the appended bytes, (byte offset, text of the tail and the appended bytes). None when nothing is appended
+    pub fn read(&mut self) -> io::Result<Option<(u64, String)>> {
+        let mut bytes = std::mem::take(&mut self.tail);
+        let tail_len = bytes.len();
+        let result = self.file.read_to_end(&mut bytes);
+        let size = bytes.len() - tail_len;
+        if size == 0 {
+            // NOTE: keep the tail on the error, it is retried by the next read
+            self.tail = bytes;
+            return result.map(|_| None);
+        }
+        if let Err(e) = result {
+            // use the bytes read before the error
+            debug!("follow: read error: {:?}", e);
+        }
+        let offset = self.tail_offset;
+        self.tail = get_tail(&bytes, self.is_hex_view).to_vec();
+        self.tail_offset = offset + (bytes.len() - self.tail.len()) as u64;
+        debug!("follow: offset={:?}, size={:?}", offset, size);
+        Ok(Some((offset, get_text(&bytes, offset, self.is_hex_view))))
+    }
+}
+
+// append the text read by Follower to the lines, the lines from the byte offset are replaced.
+// the styles are updated with is_styled, return the first replaced line index
+pub fn append_text(
+    lines: &mut ropey::Rope,
+    styles: &mut Vec<LineStyles>,
+    (offset, text): (u64, String),
+    is_hex_view: bool,
+    is_styled: bool,
+) -> usize {
+    // NOTE: the last line is not complete, it is replaced by the text with the appended bytes
+    let line_idx = if is_hex_view {
+        offset as usize / hexdump::LINE_BYTES
+    } else {
+        lines.len_lines() - 1
+    };
+    let line_idx = line_idx.min(lines.len_lines() - 1);
+    lines.remove(lines.line_to_char(line_idx)..);
+    let text = if is_styled {
+        // carry the SGR state of the previous line
+        let state = line_idx
+            .checked_sub(1)
+            .and_then(|idx| styles.get(idx))
+            .map_or(vec![], utils::ansi::get_end_state);
+        let (text, new_styles) = utils::ansi::strip_with_state(&text, state);
+        styles.truncate(line_idx);
+        styles.extend(new_styles);
+        text
+    } else {
+        text
+    };
+    lines.insert(lines.len_chars(), &text);
+    line_idx
+}
+
+// name of the input in the status line, with the compression format
+pub fn get_display_name(filename: &str, compression: Option<Compression>) -> String {
+    let name = if is_stdin(filename) {
+        "(standard input)"
+    } else {
+        filename
+    };
+    match compression {
+        Some(compression) => format!("{} ({})", name, compression.name()),
+        None => name.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::OpenOptions;
+    use std::io::Write;
+    use std::path::{Path, PathBuf};
+
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("miniless-{}-{}", std::process::id(), name))
+    }
+
+    fn append(path: &Path, bytes: &[u8]) {
+        OpenOptions::new()
+            .append(true)
+            .open(path)
+            .unwrap()
+            .write_all(bytes)
+            .unwrap();
+    }
+
+    // NOTE: don't use LESSOPEN of the environment
+    fn follow(path: &Path, hex_dump: bool) -> Follower {
+        let options = LessOptions {
+            no_lessopen: true,
+            hex_dump,
+            ..Default::default()
+        };
+        let filename = path.to_str().unwrap();
+        Follower::new(filename, &load(filename, &options).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_get_tail() {
+        assert_eq!(get_tail(b"a\nbc", false), b"bc");
+        assert_eq!(get_tail(b"a\n", false), b"");
+        assert_eq!(get_tail(b"abc", false), b"abc");
+        assert_eq!(get_tail(&[0; 20], true), &[0; 4]);
+        assert_eq!(get_tail(&[0; 16], true), b"");
+    }
+
+    #[test]
+    fn test_follower_read_text() {
+        let path = temp_path("read-text");
+        fs::write(&path, b"a\nbc").unwrap();
+        let mut follower = follow(&path, false);
+        assert_eq!(follower.read().unwrap(), None);
+
+        // the partial last line is read again with the appended bytes
+        append(&path, b"d\ne");
+        assert_eq!(follower.read().unwrap(), Some((2, String::from("bcd\ne"))));
+        append(&path, b"f\n");
+        assert_eq!(follower.read().unwrap(), Some((6, String::from("ef\n"))));
+        append(&path, b"g");
+        assert_eq!(follower.read().unwrap(), Some((9, String::from("g"))));
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_follower_read_hex() {
+        let path = temp_path("read-hex");
+        fs::write(&path, [0x41; 20]).unwrap();
+        let mut follower = follow(&path, true);
+
+        // the partial last line of 16 bytes is dumped again from its offset
+        append(&path, &[0x42; 14]);
+        let mut bytes = vec![0x41; 4];
+        bytes.extend([0x42; 14]);
+        assert_eq!(follower.read().unwrap(), Some((16, hexdump::dump(&bytes, 16))));
+        append(&path, &[0x43]);
+        assert_eq!(follower.read().unwrap(), Some((32, hexdump::dump(&[0x42, 0x42, 0x43], 32))));
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_append_text() {
+        let mut lines = ropey::Rope::from_str("a\nbc");
+        let mut styles = vec![];
+        // the partial last line is replaced
+        assert_eq!(append_text(&mut lines, &mut styles, (2, String::from("bcd\ne")), false, false), 1);
+        assert_eq!(lines.to_string(), "a\nbcd\ne");
+        assert_eq!(append_text(&mut lines, &mut styles, (6, String::from("ef\n")), false, false), 2);
+        assert_eq!(lines.to_string(), "a\nbcd\nef\n");
+        assert_eq!(append_text(&mut lines, &mut styles, (9, String::from("g")), false, false), 3);
+        assert_eq!(lines.to_string(), "a\nbcd\nef\ng");
+        assert!(styles.is_empty());
+    }
+
+    #[test]
+    fn test_append_text_styled() {
+        let (text, mut styles) = utils::ansi::strip("\x1b[31mred\npar");
+        let mut lines = ropey::Rope::from_str(&text);
+        let line_idx = append_text(&mut lines, &mut styles, (11, String::from("partial\x1b[0m\nplain\n")), false, true);
+        assert_eq!(line_idx, 1);
+        assert_eq!(lines.to_string(), "red\npartial\nplain\n");
+        // the SGR state of the previous line is carried to the replaced line
+        assert_eq!(
+            styles,
+            vec![
+                vec![(0, String::from("\x1b[31m"))],
+                vec![(0, String::from("\x1b[31m")), (7, String::from("\x1b[0m"))],
+                vec![],
+                vec![],
+            ]
+        );
+        assert_eq!(styles.len(), lines.len_lines());
+    }
+
+    #[test]
+    fn test_append_text_hex() {
+        let bytes = [0x41; 20];
+        let mut lines = ropey::Rope::from_str(&hexdump::dump(&bytes, 0));
+        let mut styles = vec![];
+        // the partial line of the offset is replaced
+        let text = hexdump::dump(&[0x41; 18][..], 16);
+        assert_eq!(append_text(&mut lines, &mut styles, (16, text), true, false), 1);
+        assert_eq!(lines.to_string(), hexdump::dump(&[0x41; 34], 0));
+        assert!(styles.is_empty());
+    }
+
+    #[test]
+    fn test_follower_truncated() {
+        let path = temp_path("truncated");
+        fs::write(&path, b"a\nb\n").unwrap();
+        let follower = follow(&path, false);
+        assert!(!follower.is_replaced());
+        OpenOptions::new().write(true).open(&path).unwrap().set_len(2).unwrap();
+        assert!(follower.is_replaced());
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_follower_rotated() {
+        let path = temp_path("rotated");
+        let rotated = temp_path("rotated.1");
+        fs::write(&path, b"a\n").unwrap();
+        let mut follower = follow(&path, false);
+
+        // keep following the rotated file until the new file is created
+        fs::rename(&path, &rotated).unwrap();
+        assert!(!follower.is_replaced());
+        append(&rotated, b"b\n");
+        assert_eq!(follower.read().unwrap(), Some((2, String::from("b\n"))));
+
+        // the new file of the same name has the other inode
+        fs::write(&path, b"new\n").unwrap();
+        assert!(follower.is_replaced());
+        fs::remove_file(&path).unwrap();
+        fs::remove_file(&rotated).unwrap();
+    }
+}
@@ -0,0 +1,306 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use crate::utils;
+use crate::utils::line::Cell;
+
+// position of the display row in the lines, (line index, wrapped row index in the line)
+pub type RowPos = (u64, u64);
+
+// cells and the first columns of the wrapped rows of a line
+#[derive(Debug)]
+pub struct LineLayout {
+    pub cells: Vec<Cell>,
+    pub row_starts: Vec<u64>,
+}
+
+// line index, width, wrap and tab width of the laid out line
+type LineKey = (u64, u64, bool, usize);
+
+// the last laid out line, share it between the layouts not to lay out a long line again for every key input.
+// clear it when the lines are changed
+#[derive(Debug, Default)]
+pub struct LineCache(RefCell<Option<(LineKey, Rc<LineLayout>)>>);
+
+impl LineCache {
+    pub fn clear(&self) {
+        *self.0.borrow_mut() = None;
+    }
+}
+
+// mapping between the logical lines of the rope and the display rows
+#[derive(Clone, Debug)]
+pub struct Layout<'a> {
+    lines: &'a ropey::Rope,
+    line_count: u64,
+    // columns of the text area
+    pub width: u64,
+    // wrap the long lines into multiple rows, or chop them
+    pub wrap: bool,
+    pub tab_width: usize,
+    // NOTE: the queries and the rendering of the rows usually repeat on the same line,
+    // keep the last line not to segment a long line into the graphemes for every row
+    cache: Rc<LineCache>,
+}
+
+impl Layout<'_> {
+    pub fn new(lines: &ropey::Rope, width: u64, wrap: bool, tab_width: usize) -> Layout<'_> {
+        Layout {
+            lines,
+            line_count: utils::line::get_line_count(lines) as u64,
+            width: width.max(1),
+            wrap,
+            tab_width,
+            cache: Rc::default(),
+        }
+    }
+
+    // share the cache of the last line with the other layouts of the same lines
+    pub fn with_cache(mut self, cache: &Rc<LineCache>) -> Self {
+        self.cache = Rc::clone(cache);
+        self
+    }
+
+    // cells of the line and the first columns of the wrapped rows, a wide char is not split into two rows
+    pub fn line_layout(&self, line_idx: u64) -> Rc<LineLayout> {
+        let key = (line_idx, self.width, self.wrap, self.tab_width);
+        if let Some((cached_key, line_layout)) = self.cache.0.borrow().as_ref() {
+            if *cached_key == key {
+                return Rc::clone(line_layout);
+            }
+        }
+        let cells = utils::line::get_cells(self.lines.line(line_idx as usize), self.tab_width);
+        let mut row_starts = vec![0];
+        if self.wrap {
+            for cell in &cells {
+                let (col, width) = (cell.col as u64, cell.width as u64);
+                let row_start = *row_starts.last().unwrap_or(&0);
+                if col + width > row_start + self.width && col > row_start {
+                    row_starts.push(col);
+                }
+            }
+        }
+        let line_layout = Rc::new(LineLayout { cells, row_starts });
+        *self.cache.0.borrow_mut() = Some((key, Rc::clone(&line_layout)));
+        line_layout
+    }
+
+    // rows of the line in the display
+    pub fn line_rows(&self, line_idx: u64) -> u64 {
+        self.rows_of(line_idx, self.lines.line(line_idx as usize))
+    }
+
+    // rows of the line slice at the line index
+    fn rows_of(&self, line_idx: u64, line: ropey::RopeSlice) -> u64 {
+        // NOTE: most lines are short, count them without laying out when advancing over many lines
+        if !self.wrap || utils::line::is_ascii_within(line, self.width as usize, self.tab_width) {
+            return 1;
+        }
+        self.line_layout(line_idx).row_starts.len() as u64
+    }
+
+    // wrapped row index in the line of the column
+    pub fn row_of_col(&self, line_idx: u64, col: u64) -> u64 {
+        if !self.wrap {
+            return 0;
+        }
+        let starts = &self.line_layout(line_idx).row_starts;
+        starts.partition_point(|start| *start <= col).max(1) as u64 - 1
+    }
+
+    // first column of the wrapped row
+    pub fn row_start_col(&self, line_idx: u64, row: u64) -> u64 {
+        if !self.wrap {
+            return 0;
+        }
+        let starts = &self.line_layout(line_idx).row_starts;
+        starts[(row as usize).min(starts.len() - 1)]
+    }
+
+    pub fn next_row(&self, (line_idx, row): RowPos) -> Option<RowPos> {
+        if row + 1 < self.line_rows(line_idx) {
+            Some((line_idx, row + 1))
+        } else if line_idx + 1 < self.line_count {
+            Some((line_idx + 1, 0))
+        } else {
+            None
+        }
+    }
+
+    // move the row position by the offset rows, stop at the first or last row
+    pub fn advance(&self, pos: RowPos, offset: i64) -> RowPos {
+        let (mut line_idx, mut row) = self.clamp(pos);
+        if !self.wrap {
+            // a row per line
+            let line_idx = if offset > 0 {
+                line_idx
+                    .saturating_add(offset as u64)
+                    .min(self.line_count.saturating_sub(1))
+            } else {
+                line_idx.saturating_sub(offset.unsigned_abs())
+            };
+            return (line_idx, 0);
+        }
+        // skip the whole lines, not to step the rows one by one
+        let mut rest = offset.unsigned_abs();
+        let mut line_iter = self.lines.lines_at(line_idx as usize);
+        if offset > 0 {
+            let mut rows = line_iter.next().map_or(1, |line| self.rows_of(line_idx, line));
+            loop {
+                let rows_after = rows - 1 - row;
+                if rest <= rows_after {
+                    return (line_idx, row + rest);
+                }
+                if line_idx + 1 >= self.line_count {
+                    return (line_idx, rows - 1);
+                }
+                rest -= rows_after + 1;
+                (line_idx, row) = (line_idx + 1, 0);
+                rows = line_iter.next().map_or(1, |line| self.rows_of(line_idx, line));
+            }
+        } else {
+            loop {
+                if rest <= row {
+                    return (line_idx, row - rest);
+                }
+                if line_idx == 0 {
+                    return (0, 0);
+                }
+                rest -= row + 1;
+                line_idx -= 1;
+                row = line_iter.prev().map_or(1, |line| self.rows_of(line_idx, line)) - 1;
+            }
+        }
+    }
+
+    // fix the row position in the lines, e.g. after the window width is changed
+    pub fn clamp(&self, (line_idx, row): RowPos) -> RowPos {
+        let line_idx = line_idx.min(self.line_count.saturating_sub(1));
+        (line_idx, row.min(self.line_rows(line_idx) - 1))
+    }
+
+    pub fn last_row(&self) -> RowPos {
+        let line_idx = self.line_count.saturating_sub(1);
+        (line_idx, self.line_rows(line_idx) - 1)
+    }
+
+    // the last top position of the display, it shows the last row at the bottom
+    pub fn last_top(&self, display_rows: u64) -> RowPos {
+        self.advance(self.last_row(), -(display_rows as i64 - 1))
+    }
+
+    // row positions of the display from the top position, until the last row of the lines
+    pub fn rows_from(&self, top: RowPos, display_rows: u64) -> Vec<RowPos> {
+        let mut rows = vec![];
+        if self.line_count == 0 {
+            return rows;
+        }
+        let mut pos = Some(self.clamp(top));
+        while let Some(p) = pos {
+            if rows.len() as u64 >= display_rows {
+                break;
+            }
+            rows.push(p);
+            pos = self.next_row(p);
+        }
+        rows
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_row_start_cols() {
+        let lines = ropey::Rope::from_str("abcdefghij\nab\n");
+        let layout = Layout::new(&lines, 4, true, 8);
+        assert_eq!(layout.line_layout(0).row_starts, vec![0, 4, 8]);
+        assert_eq!(layout.line_layout(1).row_starts, vec![0]);
+        assert_eq!(layout.row_of_col(0, 5), 1);
+        assert_eq!(layout.row_start_col(0, 2), 8);
+        // out of the rows is the last row
+        assert_eq!(layout.row_start_col(0, 9), 8);
+
+        let layout = Layout::new(&lines, 4, false, 8);
+        assert_eq!(layout.line_layout(0).row_starts, vec![0]);
+    }
+
+    #[test]
+    fn test_line_cache() {
+        let cache = Rc::new(LineCache::default());
+        let lines = ropey::Rope::from_str("abcdefghij\n");
+        let layout = Layout::new(&lines, 4, true, 8).with_cache(&cache);
+        assert_eq!(layout.line_layout(0).row_starts, vec![0, 4, 8]);
+        // the other width is not the cached line
+        let layout = Layout::new(&lines, 5, true, 8).with_cache(&cache);
+        assert_eq!(layout.line_layout(0).row_starts, vec![0, 5]);
+
+        let lines = ropey::Rope::from_str("abcdefghijkl\n");
+        let layout = Layout::new(&lines, 5, true, 8).with_cache(&cache);
+        assert_eq!(layout.line_layout(0).row_starts, vec![0, 5]);
+        cache.clear();
+        assert_eq!(layout.line_layout(0).row_starts, vec![0, 5, 10]);
+    }
+
+    #[test]
+    fn test_row_start_cols_wide_char() {
+        // the wide char at the column 3 is moved to the next row
+        let lines = ropey::Rope::from_str("abc\u{3042}d\n");
+        let layout = Layout::new(&lines, 4, true, 8);
+        assert_eq!(layout.line_layout(0).row_starts, vec![0, 3]);
+    }
+
+    #[test]
+    fn test_next_row() {
+        let lines = ropey::Rope::from_str("abcdef\nab\n");
+        let layout = Layout::new(&lines, 4, true, 8);
+        assert_eq!(layout.next_row((0, 0)), Some((0, 1)));
+        assert_eq!(layout.next_row((0, 1)), Some((1, 0)));
+        assert_eq!(layout.next_row((1, 0)), None);
+        assert_eq!(layout.advance((1, 0), -1), (0, 1));
+        assert_eq!(layout.advance((0, 0), -1), (0, 0));
+    }
+
+    #[test]
+    fn test_advance_and_clamp() {
+        let lines = ropey::Rope::from_str("abcdef\nab\nabcdefghi\n");
+        let layout = Layout::new(&lines, 4, true, 8);
+        assert_eq!(layout.advance((0, 0), 3), (2, 0));
+        assert_eq!(layout.advance((2, 0), -2), (0, 1));
+        assert_eq!(layout.advance((0, 0), 100), (2, 2));
+        assert_eq!(layout.advance((2, 2), -100), (0, 0));
+        assert_eq!(layout.clamp((10, 10)), (2, 2));
+        assert_eq!(layout.clamp((1, 5)), (1, 0));
+        // over the multiple rows of the lines
+        assert_eq!(layout.advance((0, 1), 4), (2, 2));
+        assert_eq!(layout.advance((2, 2), -4), (0, 1));
+    }
+
+    #[test]
+    fn test_advance_chop() {
+        let lines = ropey::Rope::from_str("abcdef\nab\nabcdefghi\n");
+        let layout = Layout::new(&lines, 4, false, 8);
+        assert_eq!(layout.advance((0, 0), 2), (2, 0));
+        assert_eq!(layout.advance((2, 0), -1), (1, 0));
+        assert_eq!(layout.advance((1, 0), i64::MAX), (2, 0));
+        assert_eq!(layout.advance((1, 0), i64::MIN), (0, 0));
+        assert_eq!(layout.row_of_col(2, 8), 0);
+        assert_eq!(layout.last_top(2), (1, 0));
+    }
+
+    #[test]
+    fn test_last_top_and_rows_from() {
+        let lines = ropey::Rope::from_str("abcdef\nab\nabcdefghi\n");
+        let layout = Layout::new(&lines, 4, true, 8);
+        assert_eq!(layout.last_row(), (2, 2));
+        assert_eq!(layout.last_top(3), (2, 0));
+        assert_eq!(layout.last_top(100), (0, 0));
+        assert_eq!(layout.rows_from((0, 1), 3), vec![(0, 1), (1, 0), (2, 0)]);
+        assert_eq!(layout.rows_from((2, 1), 5), vec![(2, 1), (2, 2)]);
+
+        let empty = ropey::Rope::new();
+        let layout = Layout::new(&empty, 4, true, 8);
+        assert!(layout.rows_from((0, 0), 3).is_empty());
+    }
+}
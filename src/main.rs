@@ -1,14 +1,20 @@
 use std::io;
 use std::io::stdout;
+use std::process;
 
 use clap::Parser;
 use crossterm::{
     cursor::{DisableBlinking, MoveTo},
     execute,
-    terminal::{disable_raw_mode, enable_raw_mode, Clear, ClearType},
+    terminal::{Clear, ClearType},
 };
 
 mod app;
+mod guard;
+mod input;
+mod layout;
+mod options;
+mod preprocess;
 mod renderloop;
 mod search;
 mod utils;
@@ -19,24 +25,96 @@ mod utils;
     author = env!("CARGO_PKG_AUTHORS")
 )]
 struct Opts {
-    input: String,
+    /// input file. read from stdin when omitted or "-"
+    input: Option<String>,
+    /// don't use the alternate screen, keep the last page on the terminal after exit
+    #[clap(short = 'X', long)]
+    no_init: bool,
+    /// scrolling window size of Space/f/b, half of it for Ctrl-d/Ctrl-u. negative value is the screen size minus N
+    #[clap(short = 'z', long = "window", value_name = "N", allow_negative_numbers = true)]
+    window_size: Option<i64>,
+    /// chop long lines instead of wrapping, scroll horizontally with Left/Right
+    #[clap(short = 'S', long)]
+    chop_long_lines: bool,
+    /// columns of horizontal scrolling, default is half of the screen width
+    #[clap(long, value_name = "N")]
+    shift: Option<u64>,
+    /// show line numbers, toggle with '#' while viewing
+    #[clap(short = 'N', long)]
+    line_numbers: bool,
+    /// render ANSI color escape sequences, search and cursor columns ignore them
+    #[clap(short = 'R', long)]
+    raw_control_chars: bool,
+    /// tab stops every N columns, default is 8
+    #[clap(short = 'x', long = "tabs", value_name = "N")]
+    tabs: Option<usize>,
+    /// show the hex dump view, binary files are shown as hex dump without this
+    #[clap(long, conflicts_with = "text")]
+    hex: bool,
+    /// show binary files as text, not as hex dump
+    #[clap(short = 'a', long)]
+    text: bool,
+    /// don't use the input preprocessor of the LESSOPEN environment variable
+    #[clap(short = 'L', long)]
+    no_lessopen: bool,
+    /// follow the end of the growing file like `tail -f`, stop with Ctrl-C. start with 'F' while viewing
+    #[clap(long)]
+    follow: bool,
+    /// reload when the file is changed on disk, keep the position and the search. reload with 'R' while viewing
+    #[clap(long)]
+    watch: bool,
 }
 
 fn main() -> io::Result<()> {
     let opts: Opts = Opts::parse();
     let mut stdout = stdout();
 
+    let filename = opts.input.unwrap_or(String::from(input::STDIN_FILENAME));
+    if input::is_missing_input(filename.as_str()) {
+        eprintln!("missing filename");
+        process::exit(1);
+    }
+
+    let less_options = options::LessOptions {
+        window_size: opts.window_size,
+        chop_long_lines: opts.chop_long_lines,
+        shift: opts.shift,
+        line_numbers: opts.line_numbers,
+        raw_control_chars: opts.raw_control_chars,
+        tabs: opts.tabs,
+        hex_dump: opts.hex,
+        text: opts.text,
+        no_lessopen: opts.no_lessopen,
+        follow: opts.follow,
+        watch: opts.watch,
+    };
+
     let less_app = app::MiniLessApp::new("debug.log");
 
-    enable_raw_mode()?;
+    // NOTE: load before the raw mode, the slow pipe can be interrupted by Ctrl-C while reading
+    let input = match input::load(filename.as_str(), &less_options) {
+        Ok(input) => input,
+        Err(e) => {
+            eprintln!("{}: {}", filename, e);
+            process::exit(1);
+        }
+    };
+
+    let result = {
+        let _guard = guard::TerminalGuard::new(!opts.no_init)?;
+
+        execute!(stdout, Clear(ClearType::All))?;
 
-    execute!(stdout, Clear(ClearType::All))?;
+        execute!(stdout, MoveTo(0, 0), DisableBlinking)?;
 
-    execute!(stdout, MoveTo(0, 0), DisableBlinking)?;
+        less_app.run(filename.as_str(), input, &less_options)
+    };
 
-    if let Err(e) = less_app.run(opts.input.as_str()) {
-        println!("error={:?}\r", e);
+    // NOTE: print after the terminal is restored
+    if let Err(e) = result {
+        eprintln!("{}: {}", filename, e);
+        process::exit(1);
     }
 
-    disable_raw_mode()
+    Ok(())
 }
@@ -0,0 +1,78 @@
+// display and behavior options of the pager, set by the command line arguments
+#[derive(Clone, Debug, Default)]
+pub struct LessOptions {
+    // scrolling window size of the page commands, negative value is the window rows minus the value
+    pub window_size: Option<i64>,
+    // don't wrap long lines, scroll horizontally instead
+    pub chop_long_lines: bool,
+    // columns of horizontal scrolling, default is half of the window columns
+    pub shift: Option<u64>,
+    // show the line numbers in the left gutter
+    pub line_numbers: bool,
+    // render the ANSI color escape sequences, not to print them as the text
+    pub raw_control_chars: bool,
+    // columns of the tab stops, default is 8
+    pub tabs: Option<usize>,
+    // show the hex dump view, even if the content is text
+    pub hex_dump: bool,
+    // show the binary content as text, not the hex dump view
+    pub text: bool,
+    // don't use the input preprocessor of LESSOPEN
+    pub no_lessopen: bool,
+    // start in follow mode, show the appended content of the growing file
+    pub follow: bool,
+    // reload the file when it is changed on disk
+    pub watch: bool,
+}
+
+impl LessOptions {
+    // lines of scrolling by Space/f/b
+    pub fn page_size(&self, display_rows: u64) -> u64 {
+        match self.window_size {
+            Some(n) if n > 0 => n as u64,
+            Some(n) if n < 0 => display_rows.saturating_sub(n.unsigned_abs()).max(1),
+            _ => display_rows,
+        }
+    }
+
+    // lines of scrolling by Ctrl-d/Ctrl-u
+    pub fn half_page_size(&self, display_rows: u64) -> u64 {
+        (self.page_size(display_rows) / 2).max(1)
+    }
+
+    // columns of the tab stops to expand the tabs
+    pub fn tab_width(&self) -> usize {
+        match self.tabs {
+            Some(n) if n > 0 => n,
+            _ => 8,
+        }
+    }
+
+    // columns of scrolling by Left/Right
+    pub fn shift_size(&self, window_columns: u64) -> u64 {
+        match self.shift {
+            Some(n) if n > 0 => n,
+            _ => (window_columns / 2).max(1),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tab_width() {
+        let tab_width = |tabs| {
+            LessOptions {
+                tabs,
+                ..Default::default()
+            }
+            .tab_width()
+        };
+        assert_eq!(tab_width(None), 8);
+        assert_eq!(tab_width(Some(4)), 4);
+        // -x0 falls back to the default
+        assert_eq!(tab_width(Some(0)), 8);
+    }
+}
@@ -0,0 +1,175 @@
+use log::debug;
+use std::env;
+use std::fs;
+use std::io;
+use std::process::{Command, Output, Stdio};
+
+// the input preprocessor command of less, e.g. "|pdftotext %s -" or "lesspipe.sh %s"
+const LESSOPEN: &str = "LESSOPEN";
+// the command after the preprocessor, e.g. "lesspipe.sh %s %s"
+const LESSCLOSE: &str = "LESSCLOSE";
+
+// quote the filename for the shell command
+fn quote(filename: &str) -> String {
+    format!("'{}'", filename.replace('\'', "'\\''"))
+}
+
+// replace each %s with the args in order
+fn expand(command: &str, args: &[&str]) -> String {
+    let mut args = args.iter();
+    let mut parts = command.split("%s");
+    let mut expanded = String::from(parts.next().unwrap_or(""));
+    for part in parts {
+        expanded.push_str(&args.next().map(|arg| quote(arg)).unwrap_or_default());
+        expanded.push_str(part);
+    }
+    expanded
+}
+
+// run the shell command, and get the output. the stderr is discarded not to break the screen
+fn spawn_command(command: &str) -> io::Result<Output> {
+    debug!("preprocess: command={:?}", command);
+    Command::new("sh")
+        .arg("-c")
+        .arg(command)
+        .stdin(Stdio::null())
+        .stderr(Stdio::null())
+        .output()
+}
+
+// run the shell command, and get the stdout when it succeeds
+fn run_command(command: &str) -> io::Result<Vec<u8>> {
+    let output = spawn_command(command)?;
+    if !output.status.success() {
+        return Err(io::Error::other(format!("exit status {}", output.status)));
+    }
+    Ok(output.stdout)
+}
+
+// form of the LESSOPEN command
+#[derive(Debug, PartialEq)]
+enum Lessopen<'a> {
+    // "|command %s" writes the content to stdout, the empty output falls back to the raw file.
+    // the exit status is ignored, same as less
+    Pipe(&'a str),
+    // "||command %s" is the same as Pipe, but the empty output is the content when the command succeeds
+    PipeWithStatus(&'a str),
+    // "command %s" writes the name of the replacement file
+    File(&'a str),
+}
+
+fn parse(lessopen: &str) -> Lessopen<'_> {
+    if let Some(command) = lessopen.strip_prefix("||") {
+        Lessopen::PipeWithStatus(command)
+    } else if let Some(command) = lessopen.strip_prefix('|') {
+        Lessopen::Pipe(command)
+    } else {
+        Lessopen::File(lessopen)
+    }
+}
+
+// content of the pipe preprocessor, None to fall back to the raw file
+fn get_pipe_content(lessopen: &Lessopen, output: Output) -> Option<Vec<u8>> {
+    let is_empty_content = matches!(lessopen, Lessopen::PipeWithStatus(_)) && output.status.success();
+    if output.stdout.is_empty() && !is_empty_content {
+        None
+    } else {
+        Some(output.stdout)
+    }
+}
+
+// run LESSCLOSE after the preprocessor with the filename and the replacement file
+fn close(filename: &str, replacement: &str) {
+    if let Ok(lessclose) = env::var(LESSCLOSE) {
+        if let Err(e) = run_command(&expand(&lessclose, &[filename, replacement])) {
+            debug!("preprocess close error: {:?}", e);
+        }
+    }
+}
+
+// content of the file by the preprocessor of LESSOPEN, None when it is not set or failed
+pub fn preprocess(filename: &str) -> Option<Vec<u8>> {
+    let lessopen = env::var(LESSOPEN).ok().filter(|v| !v.trim().is_empty())?;
+    // NOTE: fall back to the raw file when the preprocessor fails or writes nothing, same as less
+    let result = match parse(&lessopen) {
+        form @ (Lessopen::Pipe(command) | Lessopen::PipeWithStatus(command)) => {
+            spawn_command(&expand(command, &[filename])).map(|output| {
+                close(filename, "-");
+                get_pipe_content(&form, output)
+            })
+        }
+        Lessopen::File(command) => run_command(&expand(command, &[filename])).and_then(|stdout| {
+            let replacement = String::from_utf8_lossy(&stdout).trim().to_string();
+            if replacement.is_empty() {
+                return Ok(None);
+            }
+            let bytes = fs::read(&replacement);
+            close(filename, &replacement);
+            bytes.map(|bytes| if bytes.is_empty() { None } else { Some(bytes) })
+        }),
+    };
+
+    result.unwrap_or_else(|e| {
+        debug!("preprocess error: {:?}", e);
+        None
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_quote() {
+        assert_eq!(quote("a b.txt"), "'a b.txt'");
+        assert_eq!(quote("it's"), "'it'\\''s'");
+        assert_eq!(quote(""), "''");
+    }
+
+    #[test]
+    fn test_expand() {
+        assert_eq!(expand("lesspipe.sh %s", &["a.txt"]), "lesspipe.sh 'a.txt'");
+        assert_eq!(expand("close %s %s", &["a", "/tmp/b"]), "close 'a' '/tmp/b'");
+        // the rest of %s is empty
+        assert_eq!(expand("cmd %s %s", &["a"]), "cmd 'a' ");
+        assert_eq!(expand("cmd", &["a"]), "cmd");
+    }
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(parse("|tr a-z A-Z < %s"), Lessopen::Pipe("tr a-z A-Z < %s"));
+        assert_eq!(parse("||tr a-z A-Z < %s"), Lessopen::PipeWithStatus("tr a-z A-Z < %s"));
+        assert_eq!(parse("lesspipe.sh %s"), Lessopen::File("lesspipe.sh %s"));
+        // the command after the prefix is expanded
+        let Lessopen::PipeWithStatus(command) = parse("||cat %s") else {
+            panic!("not ||");
+        };
+        assert_eq!(expand(command, &["a.txt"]), "cat 'a.txt'");
+        let Lessopen::Pipe(command) = parse("|cat %s") else {
+            panic!("not |");
+        };
+        assert_eq!(expand(command, &["a.txt"]), "cat 'a.txt'");
+    }
+
+    #[test]
+    fn test_get_pipe_content() {
+        let pipe = parse("|cmd");
+        let pipe_with_status = parse("||cmd");
+        let output = |command| spawn_command(command).unwrap();
+        // "|" ignores the exit status
+        assert_eq!(get_pipe_content(&pipe, output("printf abc; exit 1")), Some(b"abc".to_vec()));
+        assert_eq!(get_pipe_content(&pipe, output("true")), None);
+        // "||" shows the empty output only when the command succeeds
+        assert_eq!(get_pipe_content(&pipe_with_status, output("printf abc; exit 1")), Some(b"abc".to_vec()));
+        assert_eq!(get_pipe_content(&pipe_with_status, output("true")), Some(vec![]));
+        assert_eq!(get_pipe_content(&pipe_with_status, output("exit 1")), None);
+    }
+
+    #[test]
+    fn test_run_command() {
+        assert_eq!(run_command("printf abc").unwrap(), b"abc");
+        assert!(run_command("exit 1").is_err());
+        // the filename is not interpreted by the shell
+        assert_eq!(run_command(&expand("echo %s", &["$(echo x); y"])).unwrap(), b"$(echo x); y\n");
+    }
+}
@@ -1,40 +1,72 @@
 use log::debug;
-use std::fs::File;
 use std::io;
 use std::io::stdout;
+use std::io::Write;
+use std::rc::Rc;
+use std::time::Duration;
 
 use crossterm::{
-    cursor::{position, MoveDown, MoveLeft, MoveRight, MoveTo, MoveUp, RestorePosition, SavePosition},
-    event::{read, Event, KeyCode, KeyEvent, KeyModifiers},
-    execute,
-    style::{Color, Print, ResetColor, SetBackgroundColor},
+    cursor::{position, MoveLeft, MoveTo, RestorePosition, SavePosition},
+    event::{poll, read, Event, KeyCode, KeyEvent, KeyModifiers},
+    execute, queue,
+    style::{Attribute, Color, Print, ResetColor, SetAttribute, SetBackgroundColor},
     terminal,
     terminal::{Clear, ClearType, ScrollDown, ScrollUp},
 };
+use unicode_width::UnicodeWidthStr;
 
+use crate::input;
+use crate::layout::{Layout, LineCache, RowPos};
+use crate::options::LessOptions;
 use crate::search;
-use crate::search::SearchResult;
+use crate::search::{SearchDirection, SearchResult};
 use crate::utils;
+use crate::utils::ansi::LineStyles;
 
 const DEBUG: bool = true;
 const STATUS_LINE_OFFSET: usize = 2;
-const DISPLAY_BOTTOM_LINE_OFFSET: usize = STATUS_LINE_OFFSET + 1;
-const CURSOR_JUMP_OFFSET: u16 = 30;
+// interval to check the appended content in follow mode
+const FOLLOW_INTERVAL_MS: u64 = 200;
+const FOLLOW_MESSAGE: &str = "Waiting for data... (Ctrl-C to abort)";
+const FOLLOW_RELOAD_NOTICE: &str = "file truncated/rotated, reloaded";
+const WATCH_RELOAD_NOTICE: &str = "file changed, reloaded";
 
 #[derive(Debug)]
 struct DisplayLines {
+    // line index of the first display row
     start: u64,
+    // wrapped row index in the start line
+    start_row: u64,
+    // line index of the last display row
     end: u64,
     // use with is_search_word_input_mode, (row, col)
     cursor_pos: (u64, u64),
-    // (row, col). only use col, now
+    // (line index, col). only use col, now
     shadow_cursor_pos: (u64, u64),
+    // horizontal scroll offset of the display columns, use with chop_long_lines
+    h_offset: u64,
+    chop_long_lines: bool,
+    // show the line numbers in the left gutter
+    line_numbers: bool,
+    // columns of the tab stops
+    tab_width: usize,
+    // hex dump view of the binary content, search the bytes
+    hex_view: bool,
+    // row positions of the display rows, the long lines are wrapped into multiple rows
+    rows: Vec<RowPos>,
+    // SGR styles of the lines, use with raw_control_chars
+    styles: Vec<LineStyles>,
+    // the last laid out line of the layouts, clear it when the lines are changed
+    line_cache: Rc<LineCache>,
 }
 
 impl DisplayLines {
     fn start_mut(&mut self) -> &mut u64 {
         &mut self.start
     }
+    fn start_row_mut(&mut self) -> &mut u64 {
+        &mut self.start_row
+    }
     fn end_mut(&mut self) -> &mut u64 {
         &mut self.end
     }
@@ -44,23 +76,35 @@ impl DisplayLines {
     fn shadow_cursor_pos_mut(&mut self) -> &mut (u64, u64) {
         &mut self.shadow_cursor_pos
     }
+    fn h_offset_mut(&mut self) -> &mut u64 {
+        &mut self.h_offset
+    }
+    fn line_numbers_mut(&mut self) -> &mut bool {
+        &mut self.line_numbers
+    }
+    fn rows_mut(&mut self) -> &mut Vec<RowPos> {
+        &mut self.rows
+    }
+    fn hex_view_mut(&mut self) -> &mut bool {
+        &mut self.hex_view
+    }
+    fn styles_mut(&mut self) -> &mut Vec<LineStyles> {
+        &mut self.styles
+    }
 }
 
-fn is_required_correction_cursor_col(col: u64, before_col: u64, line_len: u64) -> u16 {
+// cursor column to restore the shadow cursor column in the line, None when the cursor is already there
+fn get_correction_cursor_col(col: u64, before_col: u64, line_len: u64) -> Option<u64> {
     if col == before_col {
-        return 0;
-    }
-    if line_len > before_col {
-        return before_col as u16;
-    }
-    if line_len == 0 || line_len == 1 {
-        return 0;
+        return None;
     }
-    line_len as u16 - 1
+    Some(before_col.min(line_len.saturating_sub(1)))
 }
 
 fn clear_status_line() -> io::Result<()> {
-    let (window_columns, window_rows) = terminal::size()?;
+    let (window_columns, rows) = terminal::size()?;
+    // NOTE: clamp the rows same as the display, not to underflow in the 1 row window
+    let window_rows = get_window_rows(rows);
     let status_line = vec![" "; window_columns as usize];
 
     execute!(
@@ -74,14 +118,19 @@ fn clear_status_line() -> io::Result<()> {
     Ok(())
 }
 
+#[allow(clippy::too_many_arguments)]
 fn render_status_line(
+    display_name: &str,
     line_count: u64,
     max_line_count: usize,
     col_num: u64,
     display_lines: &DisplayLines,
     search_result: &SearchResult,
+    count: Option<usize>,
+    notice: Option<&str>,
 ) -> io::Result<()> {
-    let (window_columns, window_rows) = terminal::size()?;
+    let (window_columns, rows) = terminal::size()?;
+    let window_rows = get_window_rows(rows);
     let status_line = vec![" "; window_columns as usize];
 
     let percentage = if line_count <= 1 {
@@ -92,7 +141,8 @@ fn render_status_line(
     let l = if DEBUG {
         let (cursor_pos_col, cursor_pos_row) = position()?;
         format!(
-            "{}/{}({:3.0}%) pos={:?}, search={:?}, {:?}, {:?}",
+            "{} {}/{}({:3.0}%) pos={:?}, search={:?}, {:?}, start={:?}, end={:?}, h_offset={:?}",
+            display_name,
             line_count,
             max_line_count,
             percentage as usize,
@@ -100,13 +150,31 @@ fn render_status_line(
             search_result.word,
             search_result.match_lines.len(),
             // search_result.now_idx,
-            display_lines
+            (display_lines.start, display_lines.start_row),
+            display_lines.end,
+            display_lines.h_offset,
         )
     } else {
-        format!("{}/{}({:3.0}%)", line_count, max_line_count, percentage as usize,)
+        format!("{} {}/{}({:3.0}%)", display_name, line_count, max_line_count, percentage as usize,)
     };
 
-    let right_pane_string = format!("{}:{}", line_count, col_num);
+    let mut right_pane_string = if let Some(count) = count {
+        // show pending count prefix
+        format!("{}  {}:{}", count, line_count, col_num)
+    } else {
+        format!("{}:{}", line_count, col_num)
+    };
+    if let Some(notice) = notice {
+        let with_notice = format!("[{}]  {}", notice, right_pane_string);
+        // NOTE: drop the notice on the narrow window
+        if with_notice.width() < window_columns as usize {
+            right_pane_string = with_notice;
+        }
+    }
+    // NOTE: keep in the status line by the display width, not to overwrite the search line. e.g. CJK file name
+    let right_pane_string = utils::line::truncate_to_width(&right_pane_string, window_columns as usize);
+    let right_pane_width = right_pane_string.width();
+    let l = utils::line::truncate_to_width(&l, (window_columns as usize).saturating_sub(right_pane_width + 1));
 
     execute!(
         stdout(),
@@ -116,7 +184,7 @@ fn render_status_line(
         Print(String::from_iter(status_line)),
         MoveTo(0, window_rows - STATUS_LINE_OFFSET as u16),
         Print(l),
-        MoveTo(window_columns - right_pane_string.len() as u16, window_rows - STATUS_LINE_OFFSET as u16),
+        MoveTo(window_columns.saturating_sub(right_pane_width as u16), window_rows - STATUS_LINE_OFFSET as u16),
         Print(right_pane_string),
         ResetColor,
         RestorePosition,
@@ -126,7 +194,8 @@ fn render_status_line(
 }
 
 fn clear_search_line() -> io::Result<()> {
-    let (window_columns, window_rows) = terminal::size()?;
+    let (window_columns, rows) = terminal::size()?;
+    let window_rows = get_window_rows(rows);
     let empty_line = vec![" "; window_columns as usize];
 
     execute!(
@@ -141,41 +210,427 @@ fn clear_search_line() -> io::Result<()> {
 }
 
 fn render_search_line(search_result: &SearchResult) -> io::Result<()> {
-    let (_, window_rows) = terminal::size()?;
+    let window_rows = get_window_rows(terminal::size()?.1);
     let render_string = if search_result.word.is_empty() {
         String::from("")
     } else {
-        format!("/{}", search_result.word)
+        format!("{}{}", search_result.direction.prompt(), search_result.word)
     };
     execute!(
         stdout(),
         SavePosition,
         MoveTo(0, window_rows - STATUS_LINE_OFFSET as u16 + 1),
         Print(render_string),
+        Clear(ClearType::UntilNewLine),
         RestorePosition,
     )?;
 
     Ok(())
 }
 
-fn re_render_display_lines(lines: &ropey::Rope, start_line_num: usize, window_rows: u16) -> io::Result<()> {
-    let line_count = lines.len_lines() - 1;
+// show the search error on the search line, it is cleared by the next render_search_line
+fn render_search_error(search_word: &str, direction: SearchDirection, e: &io::Error) -> io::Result<()> {
+    let window_rows = get_window_rows(terminal::size()?.1);
+    execute!(
+        stdout(),
+        SavePosition,
+        MoveTo(0, window_rows - STATUS_LINE_OFFSET as u16 + 1),
+        Clear(ClearType::UntilNewLine),
+        Print(format!("{}{}: ", direction.prompt(), search_word)),
+        SetAttribute(Attribute::Reverse),
+        Print(e),
+        SetAttribute(Attribute::NoReverse),
+        RestorePosition,
+    )?;
 
-    for idx in 0..(window_rows - STATUS_LINE_OFFSET as u16) {
-        let offset = start_line_num + idx as usize - 1;
-        if offset >= line_count {
+    Ok(())
+}
+
+// show the message on the search line, it is cleared by the next render_search_line
+fn render_message(message: &str) -> io::Result<()> {
+    let window_rows = get_window_rows(terminal::size()?.1);
+    execute!(
+        stdout(),
+        SavePosition,
+        MoveTo(0, window_rows - STATUS_LINE_OFFSET as u16 + 1),
+        Clear(ClearType::UntilNewLine),
+        SetAttribute(Attribute::Reverse),
+        Print(message),
+        SetAttribute(Attribute::NoReverse),
+        RestorePosition,
+    )?;
+
+    Ok(())
+}
+
+// columns of the line number gutter, the digits of the last line number and a space
+fn get_gutter_width(display_lines: &DisplayLines, lines: &ropey::Rope) -> u64 {
+    if !display_lines.line_numbers {
+        return 0;
+    }
+    utils::line::get_line_count(lines).max(1).to_string().len() as u64 + 1
+}
+
+// layout of the display rows by the window width without the gutter,
+// wrap the long lines when they are not chopped
+fn get_layout<'a>(display_lines: &DisplayLines, lines: &'a ropey::Rope) -> io::Result<Layout<'a>> {
+    let (window_columns, _) = terminal::size()?;
+    let width = (window_columns as u64).saturating_sub(get_gutter_width(display_lines, lines));
+    Ok(Layout::new(lines, width, !display_lines.chop_long_lines, display_lines.tab_width)
+        .with_cache(&display_lines.line_cache))
+}
+
+// visible column range of the display row in the line
+fn get_row_range(display_lines: &DisplayLines, layout: &Layout, (line_idx, row): RowPos) -> (usize, usize) {
+    let from = if layout.wrap {
+        layout.row_start_col(line_idx, row)
+    } else {
+        display_lines.h_offset
+    };
+    (from as usize, (from + layout.width) as usize)
+}
+
+// line index and column in the line of the display position (row, col)
+fn get_text_pos(display_lines: &DisplayLines, lines: &ropey::Rope, (row, col): (u64, u64)) -> io::Result<(u64, u64)> {
+    let layout = get_layout(display_lines, lines)?;
+    let pos = display_lines
+        .rows
+        .get(row as usize)
+        .or(display_lines.rows.last())
+        .copied()
+        .unwrap_or((0, 0));
+    let (from, _) = get_row_range(display_lines, &layout, pos);
+    let gutter_width = get_gutter_width(display_lines, lines);
+    Ok((pos.0, from as u64 + col.saturating_sub(gutter_width)))
+}
+
+// print the display column range of the line at the current cursor position,
+// with the SGR styles and highlighting the search matches
+fn render_line(
+    line: ropey::RopeSlice,
+    cells: &[utils::line::Cell],
+    line_num: u64,
+    search_result: &SearchResult,
+    styles: &[(usize, String)],
+    (from, to): (usize, usize),
+) -> io::Result<()> {
+    let match_ranges = search_result.line_match_ranges(line_num);
+    let mut out = stdout();
+
+    // NOTE: the styles are cumulative, apply all styles before the range too
+    let is_styled = !styles.is_empty();
+    let mut styles = styles.iter().peekable();
+    let mut is_reverse = false;
+    // NOTE: skip the cells before the range, the long line has many rows
+    let first = cells.partition_point(|cell| cell.col < from && cell.col + cell.width <= from);
+    for cell in &cells[first..] {
+        if cell.col + cell.width > to {
             break;
         }
-        let l = lines.line(offset);
-        execute!(stdout(), MoveTo(0, idx), Print(l))?;
-        if idx as usize >= line_count - 1 {
-            break;
+        if cell.col < from {
+            // the right half of the wide char
+            if cell.col + cell.width > from {
+                queue!(out, Print(" ".repeat(cell.col + cell.width - from)))?;
+            }
+            continue;
+        }
+        while let Some((_, sequence)) = styles.next_if(|(char_idx, _)| *char_idx <= cell.char_idx) {
+            queue!(out, Print(sequence))?;
+            // the style may reset the reverse attribute
+            is_reverse = false;
+        }
+        let is_match = match_ranges
+            .iter()
+            .any(|&(start, end)| start <= cell.char_idx && cell.char_idx < end);
+        if is_match && !is_reverse {
+            queue!(out, SetAttribute(Attribute::Reverse))?;
+        } else if !is_match && is_reverse {
+            queue!(out, SetAttribute(Attribute::NoReverse))?;
+        }
+        is_reverse = is_match;
+        let c = line.char(cell.char_idx);
+        if c == '\t' {
+            queue!(out, Print(" ".repeat(cell.width)))?;
+        } else if let Some(notation) = utils::line::get_char_notation(c) {
+            queue!(out, Print(notation))?;
+        } else {
+            queue!(out, Print(line.slice(cell.char_idx..cell.char_idx + cell.char_len)))?;
+        }
+    }
+    if is_reverse {
+        queue!(out, SetAttribute(Attribute::NoReverse))?;
+    }
+    if is_styled {
+        queue!(out, SetAttribute(Attribute::Reset))?;
+    }
+    out.flush()?;
+
+    Ok(())
+}
+
+// render the display rows [from_row, to_row) of the display lines
+fn render_display_rows(
+    display_lines: &DisplayLines,
+    lines: &ropey::Rope,
+    from_row: u64,
+    to_row: u64,
+    search_result: &SearchResult,
+) -> io::Result<()> {
+    let layout = get_layout(display_lines, lines)?;
+    let gutter_width = get_gutter_width(display_lines, lines) as usize;
+    for row in from_row..to_row {
+        execute!(stdout(), MoveTo(0, row as u16), Clear(ClearType::UntilNewLine))?;
+        if let Some(&pos) = display_lines.rows.get(row as usize) {
+            if gutter_width > 0 {
+                // line number only on the first row of the wrapped line
+                let gutter = if pos.1 == 0 {
+                    format!("{:>1$} ", pos.0 + 1, gutter_width - 1)
+                } else {
+                    " ".repeat(gutter_width)
+                };
+                execute!(stdout(), Print(gutter))?;
+            }
+            let range = get_row_range(display_lines, &layout, pos);
+            let styles = display_lines
+                .styles
+                .get(pos.0 as usize)
+                .map(Vec::as_slice)
+                .unwrap_or_default();
+            let line = lines.line(pos.0 as usize);
+            let line_layout = layout.line_layout(pos.0);
+            render_line(line, &line_layout.cells, pos.0 + 1, search_result, styles, range)?;
+        }
+    }
+
+    Ok(())
+}
+
+// set the display rows from the top position
+fn set_display_rows(display_lines: &mut DisplayLines, layout: &Layout, window_rows: u16, top: RowPos) {
+    let display_rows = window_rows as u64 - STATUS_LINE_OFFSET as u64;
+    let rows = layout.rows_from(top, display_rows);
+    let (start, start_row) = rows.first().copied().unwrap_or((0, 0));
+    *display_lines.start_mut() = start;
+    *display_lines.start_row_mut() = start_row;
+    *display_lines.end_mut() = rows.last().map_or(0, |pos| pos.0);
+    *display_lines.rows_mut() = rows;
+}
+
+// redraw all display rows from the top position
+fn re_render_display_lines(
+    display_lines: &mut DisplayLines,
+    window_rows: u16,
+    lines: &ropey::Rope,
+    search_result: &SearchResult,
+    top: RowPos,
+) -> io::Result<()> {
+    let layout = get_layout(display_lines, lines)?;
+    set_display_rows(display_lines, &layout, window_rows, top);
+    render_display_rows(display_lines, lines, 0, window_rows as u64 - STATUS_LINE_OFFSET as u64, search_result)?;
+    render_search_line(search_result)
+}
+
+// jump to the search result line, and move cursor to the match position
+fn jump_to_search_result(
+    display_lines: &mut DisplayLines,
+    window_rows: u16,
+    lines: &ropey::Rope,
+    search_result: &SearchResult,
+    options: &LessOptions,
+    (lnum, lcol): (u64, u64),
+) -> io::Result<()> {
+    // NOTE: the match column is the char index, the cursor column is the display column
+    let col = utils::line::char_to_col(lines.line(lnum as usize - 1), lcol as usize, display_lines.tab_width) as u64;
+    scroll_display_lines(display_lines, window_rows, lines, search_result, (lnum - 1, 0))?;
+    *display_lines.shadow_cursor_pos_mut() = (lnum - 1, col);
+    move_cursor_to_col(display_lines, window_rows, lines, search_result, options, (lnum - 1, col))
+}
+
+// move cursor to the line, scroll the display lines when the line is out of the display
+fn jump_to_line(
+    display_lines: &mut DisplayLines,
+    window_rows: u16,
+    lines: &ropey::Rope,
+    search_result: &SearchResult,
+    options: &LessOptions,
+    line_idx: u64,
+) -> io::Result<()> {
+    let layout = get_layout(display_lines, lines)?;
+    let display_rows = window_rows as u64 - STATUS_LINE_OFFSET as u64;
+    let (line_idx, _) = layout.clamp((line_idx, 0));
+    if line_idx < display_lines.start || line_idx > display_lines.end {
+        // NOTE: keep the last page filled
+        let top = (line_idx, 0).min(layout.last_top(display_rows));
+        scroll_display_lines(display_lines, window_rows, lines, search_result, top)?;
+    }
+    *display_lines.shadow_cursor_pos_mut() = (line_idx, 0);
+    move_cursor_to_col(display_lines, window_rows, lines, search_result, options, (line_idx, 0))
+}
+
+// scroll the display rows to the new top position, redraw only the scrolled rows when possible
+fn scroll_display_lines(
+    display_lines: &mut DisplayLines,
+    window_rows: u16,
+    lines: &ropey::Rope,
+    search_result: &SearchResult,
+    top: RowPos,
+) -> io::Result<()> {
+    let layout = get_layout(display_lines, lines)?;
+    let display_rows = window_rows as u64 - STATUS_LINE_OFFSET as u64;
+    let top = layout.clamp(top);
+    let now_top = (display_lines.start, display_lines.start_row);
+    if top == now_top {
+        return Ok(());
+    }
+
+    let now_rows = display_lines.rows.clone();
+    set_display_rows(display_lines, &layout, window_rows, top);
+    if let Some(offset) = now_rows.iter().position(|pos| *pos == top) {
+        execute!(stdout(), ScrollUp(offset as u16))?;
+        render_display_rows(display_lines, lines, display_rows - offset as u64, display_rows, search_result)?;
+    } else if let Some(offset) = display_lines.rows.iter().position(|pos| *pos == now_top) {
+        execute!(stdout(), ScrollDown(offset as u16))?;
+        render_display_rows(display_lines, lines, 0, offset as u64, search_result)?;
+    } else {
+        render_display_rows(display_lines, lines, 0, display_rows, search_result)?;
+    }
+    render_search_line(search_result)
+}
+
+// move cursor to the column of the line, scroll the display when the column is out of the display
+fn move_cursor_to_col(
+    display_lines: &mut DisplayLines,
+    window_rows: u16,
+    lines: &ropey::Rope,
+    search_result: &SearchResult,
+    options: &LessOptions,
+    (line_idx, col): (u64, u64),
+) -> io::Result<()> {
+    let layout = get_layout(display_lines, lines)?;
+    let display_rows = window_rows as u64 - STATUS_LINE_OFFSET as u64;
+    let cursor_row = layout.clamp((line_idx, layout.row_of_col(line_idx, col)));
+
+    // scroll vertically until the cursor row is in the display
+    let now_top = (display_lines.start, display_lines.start_row);
+    let top = match display_lines.rows.last() {
+        _ if cursor_row < now_top => cursor_row,
+        Some(&last) if cursor_row > last => layout.advance(cursor_row, -(display_rows as i64 - 1)),
+        _ => now_top,
+    };
+
+    // scroll horizontally until the cursor column is in the display
+    let mut h_offset = display_lines.h_offset;
+    if !layout.wrap {
+        let step = options.shift_size(layout.width);
+        while col < h_offset {
+            h_offset = h_offset.saturating_sub(step);
+        }
+        while col >= h_offset + layout.width {
+            h_offset += step;
         }
     }
+    if h_offset != display_lines.h_offset {
+        *display_lines.h_offset_mut() = h_offset;
+        re_render_display_lines(display_lines, window_rows, lines, search_result, top)?;
+    } else {
+        scroll_display_lines(display_lines, window_rows, lines, search_result, top)?;
+    }
+
+    let row = display_lines
+        .rows
+        .iter()
+        .position(|pos| *pos == cursor_row)
+        .unwrap_or(0);
+    let (from, _) = get_row_range(display_lines, &layout, cursor_row);
+    let gutter_width = get_gutter_width(display_lines, lines);
+    execute!(stdout(), MoveTo((gutter_width + col - from as u64) as u16, row as u16))?;
+
+    Ok(())
+}
+
+// scroll the display columns to the new offset, and redraw all
+fn scroll_horizontally(
+    display_lines: &mut DisplayLines,
+    window_rows: u16,
+    lines: &ropey::Rope,
+    search_result: &SearchResult,
+    h_offset: u64,
+) -> io::Result<()> {
+    if h_offset == display_lines.h_offset {
+        return Ok(());
+    }
+    *display_lines.h_offset_mut() = h_offset;
+    // NOTE: not to use SavePosition, render_search_line overwrites it
+    let (cursor_pos_col, cursor_pos_row) = position()?;
+    let top = (display_lines.start, display_lines.start_row);
+    re_render_display_lines(display_lines, window_rows, lines, search_result, top)?;
+    execute!(stdout(), MoveTo(cursor_pos_col, cursor_pos_row))?;
 
     Ok(())
 }
 
+// move cursor to the line with keeping the shadow cursor column, scroll the display lines when required
+fn move_cursor_to_line(
+    display_lines: &mut DisplayLines,
+    window_rows: u16,
+    lines: &ropey::Rope,
+    search_result: &SearchResult,
+    options: &LessOptions,
+    cursor_col: u64,
+    line_idx: u64,
+) -> io::Result<()> {
+    // reset cursor position when line length is shorter than cursor position,
+    // and restore the shadow cursor position when the line is enough long
+    let before_cursor_pos_col = display_lines.shadow_cursor_pos.1;
+    let layout = get_layout(display_lines, lines)?;
+    let cursor_cols = utils::line::get_cells_cursor_cols(&layout.line_layout(line_idx).cells);
+    let line_len = *cursor_cols.last().unwrap_or(&0) as u64 + 1;
+    let col = cursor_col.min(line_len - 1);
+    let col = get_correction_cursor_col(col, before_cursor_pos_col, line_len).unwrap_or(col);
+    // not to place the cursor on the right half of the wide char
+    let col = cursor_cols[cursor_cols.partition_point(|c| *c as u64 <= col).max(1) - 1] as u64;
+
+    *display_lines.shadow_cursor_pos_mut() = (line_idx, before_cursor_pos_col);
+    move_cursor_to_col(display_lines, window_rows, lines, search_result, options, (line_idx, col))
+}
+
+// scroll display rows and cursor together by the offset rows, until the first or last page
+#[allow(clippy::too_many_arguments)]
+fn scroll_lines_with_cursor(
+    display_lines: &mut DisplayLines,
+    window_rows: u16,
+    lines: &ropey::Rope,
+    search_result: &SearchResult,
+    options: &LessOptions,
+    cursor_col: u64,
+    now_line_idx: usize,
+    offset: i64,
+) -> io::Result<()> {
+    let layout = get_layout(display_lines, lines)?;
+    let display_rows = window_rows as u64 - STATUS_LINE_OFFSET as u64;
+    let now_top = (display_lines.start, display_lines.start_row);
+    let top = if offset < 0 {
+        layout.advance(now_top, offset)
+    } else {
+        let last_top = layout.last_top(display_rows).max(now_top);
+        layout.advance(now_top, offset).min(last_top)
+    };
+    // keep the cursor column in the display row
+    let now_line_idx = now_line_idx as u64;
+    let cursor_row = layout.row_of_col(now_line_idx, cursor_col);
+    let (line_idx, row) = layout.advance((now_line_idx, cursor_row), offset);
+    let col = layout.row_start_col(line_idx, row) + cursor_col - layout.row_start_col(now_line_idx, cursor_row);
+    debug!("scroll: offset={:?}, line_idx={:?}, top={:?}", offset, line_idx, top);
+    scroll_display_lines(display_lines, window_rows, lines, search_result, top)?;
+    move_cursor_to_line(display_lines, window_rows, lines, search_result, options, col, line_idx)
+}
+
+// rows of scrolling by the repeated command, saturate not to overflow by the huge count
+fn get_scroll_offset(size: u64, repeat: usize) -> i64 {
+    size.saturating_mul(repeat as u64).min(i64::MAX as u64) as i64
+}
+
+#[allow(clippy::collapsible_match)]
 fn handler_search_word_input_mode(
     display_lines: &mut DisplayLines,
     window_rows: u16,
@@ -183,6 +638,7 @@ fn handler_search_word_input_mode(
     event: &Event,
     is_search_word_input_mode: bool,
     search_result: &mut SearchResult,
+    options: &LessOptions,
 ) -> io::Result<bool> {
     let mut return_search_word_input_mode = is_search_word_input_mode;
     match event {
@@ -210,43 +666,66 @@ fn handler_search_word_input_mode(
             code: KeyCode::Enter, ..
         }) => {
             let word_vec = search_result.word_vec.clone();
-            search_result.reset();
+            let direction = search_result.input_direction;
 
             // set search word
-            let mut lcol = display_lines.cursor_pos.1;
+            let mut search_result_pos = None;
             if !word_vec.is_empty() {
-                *search_result.word_mut() = String::from_iter(word_vec.clone());
-
-                // get search result
-                let result = search::search(search_result.filename, search_result.word.as_str())?;
-                if !result.is_empty() {
-                    // set search result
-                    *search_result.match_lines_mut() = result;
-
-                    let now_position_row = display_lines.start + display_lines.cursor_pos.0;
-                    let now_position_col = display_lines.cursor_pos.1;
-                    if let Some((lnum, _lcol)) = search_result.get_near_line((now_position_row, now_position_col)) {
-                        lcol = _lcol;
-                        // jump to result line
-                        execute!(stdout(), RestorePosition, SavePosition, Clear(ClearType::All))?;
-
-                        re_render_display_lines(lines, lnum as usize, window_rows)?;
-                        render_search_line(search_result)?;
-
-                        *display_lines.start_mut() = lnum - 1;
-                        // TODO: check this
-                        *display_lines.end_mut() = lnum - 1;
-                        // *display_lines.end_mut() = lnum + window_rows as u64 - STATUS_LINE_OFFSET as u64 - 1;
-                        execute!(stdout(), MoveTo(0, 0))?;
+                let word = String::from_iter(word_vec.clone());
+
+                // get search result, keep the previous search when the pattern is invalid
+                let searched = search::build_matcher(word.as_str()).and_then(|matcher| {
+                    let result = if display_lines.hex_view {
+                        search::search_hexdump(lines, word.as_str(), &matcher)
+                    } else {
+                        search::search(lines, word.as_str(), &matcher)
+                    };
+                    result.map(|r| (matcher, r))
+                });
+                match searched {
+                    Ok((matcher, result)) => {
+                        search_result.reset();
+                        *search_result.direction_mut() = direction;
+                        *search_result.word_mut() = word;
+                        *search_result.matcher_mut() = Some(matcher);
+                        if !result.is_empty() {
+                            // set search result
+                            *search_result.match_lines_mut() = result;
+
+                            let (line_idx, col) = get_text_pos(display_lines, lines, display_lines.cursor_pos)?;
+                            let col = utils::line::col_to_char(
+                                lines.line(line_idx as usize),
+                                col as usize,
+                                display_lines.tab_width,
+                            );
+                            let now_position = (line_idx + 1, col as u64);
+                            search_result_pos = match direction {
+                                SearchDirection::Forward => search_result.get_near_line(now_position),
+                                SearchDirection::Backward => search_result.get_near_line_with_previous(now_position),
+                            };
+                        }
+                        // redraw the highlights of the new search
+                        let top = (display_lines.start, display_lines.start_row);
+                        re_render_display_lines(display_lines, window_rows, lines, search_result, top)?;
+                    }
+                    Err(e) => {
+                        debug!("search error: {:?}", e);
+                        render_search_error(word.as_str(), direction, &e)?;
                     }
                 }
             } else {
+                search_result.reset();
+                let top = (display_lines.start, display_lines.start_row);
+                re_render_display_lines(display_lines, window_rows, lines, search_result, top)?;
                 clear_search_line()?;
             }
 
             return_search_word_input_mode = false;
-            execute!(stdout(), MoveTo(0, 0))?;
-            execute!(stdout(), MoveTo(lcol as u16, 0))?;
+            if let Some(pos) = search_result_pos {
+                jump_to_search_result(display_lines, window_rows, lines, search_result, options, pos)?;
+            } else {
+                execute!(stdout(), MoveTo(display_lines.cursor_pos.1 as u16, display_lines.cursor_pos.0 as u16))?;
+            }
             *search_result.word_vec_mut() = Vec::new();
         }
         Event::Key(KeyEvent {
@@ -260,7 +739,7 @@ fn handler_search_word_input_mode(
     Ok(return_search_word_input_mode)
 }
 
-#[allow(clippy::too_many_arguments)]
+#[allow(clippy::too_many_arguments, clippy::collapsible_match)]
 fn handler_display_input_mode(
     display_lines: &mut DisplayLines,
     window_rows: u16,
@@ -272,166 +751,111 @@ fn handler_display_input_mode(
     event: &Event,
     is_search_word_input_mode: bool,
     search_result: &mut SearchResult,
+    count: Option<usize>,
+    options: &LessOptions,
 ) -> io::Result<bool> {
     let mut return_search_word_input_mode = is_search_word_input_mode;
+    // repeat count of the motion command
+    let repeat = count.unwrap_or(1).max(1);
+    let display_rows = window_rows as u64 - STATUS_LINE_OFFSET as u64;
+    let layout = get_layout(display_lines, lines)?;
+    // cursor column in the line
+    let (_, cursor_col) = get_text_pos(display_lines, lines, (cursor_pos_row as u64, cursor_pos_col as u64))?;
     let now_line = lines.line(now_line_idx);
-    let line_len = if let Some(v) = now_line.as_str() {
-        v.trim_end().len()
-    } else {
-        0
-    };
+    // move the cursor by the graphemes
+    let cursor_cols = utils::line::get_cells_cursor_cols(&layout.line_layout(now_line_idx as u64).cells);
+    let cursor_col_idx = cursor_cols.partition_point(|col| *col as u64 <= cursor_col).max(1) - 1;
 
     match event {
         Event::Key(KeyEvent {
             code: KeyCode::Char('j') | KeyCode::Down,
             ..
         }) => {
-            let mut col_diff = 0;
-            let mut next_line_len = 0;
-            // save cursor position before move
-            let before_cursor_pos_col = display_lines.shadow_cursor_pos.1;
-
-            if (window_rows - DISPLAY_BOTTOM_LINE_OFFSET as u16) == cursor_pos_row
-                && line_count != (display_lines.end + 1) as usize
-            {
-                *display_lines.start_mut() = display_lines.start + 1;
-                *display_lines.end_mut() = display_lines.end + 1;
-                let l = lines.line(display_lines.end as usize);
-                // NOTE: use format because debug print
-                execute!(
-                    stdout(),
-                    ScrollUp(1),
-                    SavePosition,
-                    MoveLeft(cursor_pos_col),
-                    Print(format!("{}", l)),
-                    RestorePosition
-                )?;
-                *display_lines.shadow_cursor_pos_mut() = (cursor_pos_row as u64 + 1, before_cursor_pos_col);
-
-                // TODO: last line
-                let now_line = lines.line(now_line_idx + 1);
-                next_line_len = utils::line::get_stripped_line_length(now_line);
-                next_line_len = next_line_len.saturating_sub(1);
-                if cursor_pos_col > next_line_len as u16 {
-                    col_diff = cursor_pos_col - next_line_len as u16;
-                }
-            } else if (window_rows - DISPLAY_BOTTOM_LINE_OFFSET as u16) != cursor_pos_row
-                && line_count != (cursor_pos_row + 1) as usize
-            {
-                execute!(stdout(), MoveDown(1))?;
-                *display_lines.shadow_cursor_pos_mut() = (cursor_pos_row as u64 + 1, before_cursor_pos_col);
-
-                // reset cursor position when line length is shorter than cursor position
-                let now_line = lines.line(now_line_idx + 1);
-                next_line_len = utils::line::get_stripped_line_length(now_line);
-                next_line_len = next_line_len.saturating_sub(1);
-                if cursor_pos_col > next_line_len as u16 {
-                    col_diff = cursor_pos_col - next_line_len as u16;
-                }
-            }
-
-            if col_diff > 0 {
-                let _shadow_cursor_pos_col = display_lines.shadow_cursor_pos.1;
-                *display_lines.shadow_cursor_pos_mut() = (cursor_pos_row as u64, _shadow_cursor_pos_col);
-                execute!(stdout(), MoveLeft(col_diff))?;
-            }
-
-            // fix col position for shadow cursor
-            let shadow_cursor_col_diff = is_required_correction_cursor_col(
-                cursor_pos_col as u64,
-                before_cursor_pos_col,
-                next_line_len as u64 + 1,
-            );
-            if shadow_cursor_col_diff > 0 {
-                let mut d = shadow_cursor_col_diff as i16 - cursor_pos_col as i16;
-                if d < 0 {
-                    d = 0;
-                }
-                execute!(stdout(), MoveRight(d as u16))?;
-                *display_lines.shadow_cursor_pos_mut() = (cursor_pos_row as u64, before_cursor_pos_col);
-            }
+            let line_idx = now_line_idx.saturating_add(repeat).min(line_count.saturating_sub(1));
+            move_cursor_to_line(
+                display_lines,
+                window_rows,
+                lines,
+                search_result,
+                options,
+                cursor_col,
+                line_idx as u64,
+            )?;
         }
         Event::Key(KeyEvent {
             code: KeyCode::Char('k') | KeyCode::Up,
             ..
         }) => {
-            let mut col_diff = 0;
-            let mut prev_line_len = 0;
-            // save cursor position before move
-            let before_cursor_pos_col = display_lines.shadow_cursor_pos.1;
-
-            if 0 == cursor_pos_row && display_lines.start > 0 {
-                *display_lines.start_mut() = display_lines.start - 1;
-                *display_lines.end_mut() = display_lines.end - 1;
-                let l = lines.line(display_lines.start as usize);
-                // NOTE: use format because debug print
-                execute!(
-                    stdout(),
-                    ScrollDown(1),
-                    SavePosition,
-                    MoveLeft(cursor_pos_col),
-                    Print(format!("{}", l)),
-                    RestorePosition
-                )?;
-                *display_lines.shadow_cursor_pos_mut() = (cursor_pos_row as u64 - 1, before_cursor_pos_col);
-
-                // TODO: first line
-                let now_line = lines.line(now_line_idx - 1);
-                prev_line_len = utils::line::get_stripped_line_length(now_line);
-                prev_line_len = prev_line_len.saturating_sub(1);
-                if cursor_pos_col > prev_line_len as u16 {
-                    col_diff = cursor_pos_col - prev_line_len as u16;
-                }
-            } else if 0 != cursor_pos_row {
-                execute!(stdout(), MoveUp(1))?;
-                *display_lines.shadow_cursor_pos_mut() = (cursor_pos_row as u64 - 1, before_cursor_pos_col);
-
-                // reset cursor position when line length is shorter than cursor position
-                let now_line = lines.line(now_line_idx - 1);
-                prev_line_len = utils::line::get_stripped_line_length(now_line);
-                prev_line_len = prev_line_len.saturating_sub(1);
-                if cursor_pos_col > prev_line_len as u16 {
-                    col_diff = cursor_pos_col - prev_line_len as u16;
-                }
-            }
-
-            if col_diff > 0 {
-                let _shadow_cursor_pos_col = display_lines.shadow_cursor_pos.1;
-                *display_lines.shadow_cursor_pos_mut() = (cursor_pos_row as u64, _shadow_cursor_pos_col);
-                execute!(stdout(), MoveLeft(col_diff))?;
-            }
-
-            // fix col position for shadow cursor
-            let shadow_cursor_col_diff = is_required_correction_cursor_col(
-                cursor_pos_col as u64,
-                before_cursor_pos_col,
-                prev_line_len as u64 + 1,
-            );
-            if shadow_cursor_col_diff > 0 {
-                let mut d = shadow_cursor_col_diff as i16 - cursor_pos_col as i16;
-                if d < 0 {
-                    d = 0;
-                }
-                execute!(stdout(), MoveRight(d as u16))?;
-                *display_lines.shadow_cursor_pos_mut() = (cursor_pos_row as u64, before_cursor_pos_col);
-            }
+            let line_idx = now_line_idx.saturating_sub(repeat);
+            move_cursor_to_line(
+                display_lines,
+                window_rows,
+                lines,
+                search_result,
+                options,
+                cursor_col,
+                line_idx as u64,
+            )?;
+        }
+        Event::Key(KeyEvent {
+            code: KeyCode::Left, ..
+        }) if display_lines.chop_long_lines => {
+            // scroll horizontally, keep the cursor position in the window
+            let step = options.shift_size(layout.width).saturating_mul(repeat as u64);
+            let h_offset = display_lines.h_offset.saturating_sub(step);
+            let col = cursor_col - display_lines.h_offset + h_offset;
+            scroll_horizontally(display_lines, window_rows, lines, search_result, h_offset)?;
+            *display_lines.shadow_cursor_pos_mut() = (now_line_idx as u64, col);
+        }
+        Event::Key(KeyEvent {
+            code: KeyCode::Right, ..
+        }) if display_lines.chop_long_lines => {
+            // scroll horizontally until the end of the longest line in the display
+            let step = options.shift_size(layout.width).saturating_mul(repeat as u64);
+            let max_line_len = (display_lines.start..=display_lines.end)
+                .map(|idx| utils::line::get_line_width(lines.line(idx as usize), display_lines.tab_width) as u64)
+                .max()
+                .unwrap_or(0);
+            let h_offset = display_lines
+                .h_offset
+                .saturating_add(step)
+                .min(max_line_len.saturating_sub(1).max(display_lines.h_offset));
+            let col = cursor_col - display_lines.h_offset + h_offset;
+            scroll_horizontally(display_lines, window_rows, lines, search_result, h_offset)?;
+            *display_lines.shadow_cursor_pos_mut() = (now_line_idx as u64, col);
         }
         Event::Key(KeyEvent {
             code: KeyCode::Char('h') | KeyCode::Left,
             ..
         }) => {
-            if cursor_pos_col > 0 {
-                *display_lines.shadow_cursor_pos_mut() = (cursor_pos_row as u64, cursor_pos_col as u64 - 1);
-                execute!(stdout(), MoveLeft(1))?
+            if cursor_col_idx > 0 {
+                let col = cursor_cols[cursor_col_idx.saturating_sub(repeat)] as u64;
+                *display_lines.shadow_cursor_pos_mut() = (now_line_idx as u64, col);
+                move_cursor_to_col(
+                    display_lines,
+                    window_rows,
+                    lines,
+                    search_result,
+                    options,
+                    (now_line_idx as u64, col),
+                )?;
             }
         }
         Event::Key(KeyEvent {
             code: KeyCode::Char('l') | KeyCode::Right,
             ..
         }) => {
-            if line_len as u16 - 1 > cursor_pos_col {
-                *display_lines.shadow_cursor_pos_mut() = (cursor_pos_row as u64, cursor_pos_col as u64 + 1);
-                execute!(stdout(), MoveRight(1))?
+            if cursor_col_idx + 1 < cursor_cols.len() {
+                let col = cursor_cols[cursor_col_idx.saturating_add(repeat).min(cursor_cols.len() - 1)] as u64;
+                *display_lines.shadow_cursor_pos_mut() = (now_line_idx as u64, col);
+                move_cursor_to_col(
+                    display_lines,
+                    window_rows,
+                    lines,
+                    search_result,
+                    options,
+                    (now_line_idx as u64, col),
+                )?;
             }
         }
         Event::Key(KeyEvent {
@@ -439,161 +863,128 @@ fn handler_display_input_mode(
             modifiers: KeyModifiers::CONTROL,
             ..
         }) => {
-            let mut scroll_offset: u16 = CURSOR_JUMP_OFFSET;
-            let display_line_start = if display_lines.start <= CURSOR_JUMP_OFFSET as u64 {
-                scroll_offset = if display_lines.start == 0 {
-                    0
-                } else {
-                    CURSOR_JUMP_OFFSET - display_lines.start as u16
-                };
-                1
-            } else {
-                display_lines.start - CURSOR_JUMP_OFFSET as u64 + 1
-            };
-
-            if scroll_offset > 0 {
-                execute!(stdout(), ScrollDown(scroll_offset))?;
-
-                *display_lines.start_mut() = display_line_start - 1;
-                *display_lines.end_mut() = display_line_start + window_rows as u64 - STATUS_LINE_OFFSET as u64 - 2;
-
-                execute!(stdout(), SavePosition, Clear(ClearType::All))?;
-                re_render_display_lines(lines, display_line_start as usize, window_rows)?;
-                execute!(stdout(), RestorePosition)?;
-            }
-            let mut jump_offset = CURSOR_JUMP_OFFSET - scroll_offset;
-            if jump_offset > 0 {
-                let check_offset = 0; //cursor_pos_row - jump_offset;
-                if jump_offset > cursor_pos_row {
-                    jump_offset -= cursor_pos_row;
-                }
-                if jump_offset > 0 {
-                    execute!(stdout(), MoveUp(jump_offset))?;
-                }
-
-                // for debug
-                if false {
-                    execute!(
-                        stdout(),
-                        SavePosition,
-                        Print(format!(
-                            "pos={:?},sc={:?},jmp={:?},check={:?},lines.end={:?},line_start={:?},",
-                            cursor_pos_row,
-                            scroll_offset,
-                            jump_offset,
-                            check_offset,
-                            display_lines.end,
-                            display_line_start
-                        )),
-                        RestorePosition
-                    )?
-                }
-            }
+            let offset = get_scroll_offset(options.half_page_size(display_rows), repeat);
+            scroll_lines_with_cursor(
+                display_lines,
+                window_rows,
+                lines,
+                search_result,
+                options,
+                cursor_col,
+                now_line_idx,
+                -offset,
+            )?;
         }
         Event::Key(KeyEvent {
             code: KeyCode::Char('d'),
             modifiers: KeyModifiers::CONTROL,
             ..
         }) => {
-            let mut scroll_offset: u16 = CURSOR_JUMP_OFFSET;
-            let mut display_line_end = now_line_idx + CURSOR_JUMP_OFFSET as usize + window_rows as usize
-                - STATUS_LINE_OFFSET
-                - cursor_pos_row as usize;
-            if display_line_end > line_count - 1 {
-                scroll_offset = CURSOR_JUMP_OFFSET - (display_line_end - line_count - 1) as u16;
-                display_line_end = line_count - 1;
-            }
-            if display_line_end == line_count - 1 && display_lines.end == display_line_end as u64 {
-                scroll_offset = 0;
-            }
-            if scroll_offset > 0 {
-                execute!(stdout(), ScrollUp(scroll_offset))?;
-
-                execute!(stdout(), SavePosition, Clear(ClearType::All))?;
-                let line_start_num = now_line_idx + scroll_offset as usize;
-                let line_start_idx = line_start_num - 1;
-                re_render_display_lines(lines, line_start_num, window_rows)?;
-                execute!(stdout(), RestorePosition)?;
-                *display_lines.start_mut() = line_start_idx as u64;
-                *display_lines.end_mut() = display_line_end as u64;
-                debug!(
-                    "Ctrl-d: scroll_offset sc={:?}, line_end={:?}, count={:?}, display_lines={:?}",
-                    scroll_offset,
-                    display_line_end,
-                    line_count - 1,
-                    display_lines
-                );
-            }
-            let mut jump_offset = CURSOR_JUMP_OFFSET - scroll_offset;
-            if jump_offset > 0 {
-                let check_offset = display_lines.start as u16 + cursor_pos_row + jump_offset;
-                if check_offset > line_count as u16 {
-                    jump_offset = line_count as u16 - display_lines.start as u16 - cursor_pos_row - 1;
-                    debug!("Ctrl-d: over line_count jmp={:?}", jump_offset);
-                } else if check_offset > display_line_end as u16 {
-                    jump_offset = window_rows - cursor_pos_row - STATUS_LINE_OFFSET as u16 - 1;
-                    debug!("Ctrl-d: jmp={:?}", jump_offset);
-                }
-                if jump_offset > 0 {
-                    execute!(stdout(), MoveDown(jump_offset))?;
-                }
-
-                debug!(
-                    "pos={:?},sc={:?},jmp={:?},check={:?},lines.end={:?},line_end={:?},",
-                    cursor_pos_row, scroll_offset, jump_offset, check_offset, display_lines.end, display_line_end
-                );
-            }
+            let offset = get_scroll_offset(options.half_page_size(display_rows), repeat);
+            scroll_lines_with_cursor(
+                display_lines,
+                window_rows,
+                lines,
+                search_result,
+                options,
+                cursor_col,
+                now_line_idx,
+                offset,
+            )?;
         }
         Event::Key(KeyEvent {
-            code: KeyCode::Char('/'),
+            code: KeyCode::Char('b') | KeyCode::PageUp,
             ..
         }) => {
-            return_search_word_input_mode = true;
-            *display_lines.cursor_pos_mut() = (cursor_pos_row as u64, cursor_pos_col as u64);
-            clear_search_line()?;
-            execute!(stdout(), SavePosition, MoveTo(0, window_rows + 1), Print("/"))?;
+            let offset = get_scroll_offset(options.page_size(display_rows), repeat);
+            scroll_lines_with_cursor(
+                display_lines,
+                window_rows,
+                lines,
+                search_result,
+                options,
+                cursor_col,
+                now_line_idx,
+                -offset,
+            )?;
         }
         Event::Key(KeyEvent {
-            code: KeyCode::Char('n'),
+            code: KeyCode::Char(' ' | 'f') | KeyCode::PageDown,
             ..
         }) => {
-            // jump next search result
-            if search_result.clone().exists_match() {
-                let now_position_row = now_line_idx as u64 + 2;
-                let now_position_col = display_lines.cursor_pos.1;
-                if let Some((lnum, lcol)) = search_result.get_near_line((now_position_row, now_position_col)) {
-                    // jump to result line
-                    execute!(stdout(), RestorePosition, SavePosition, Clear(ClearType::All))?;
-
-                    re_render_display_lines(lines, lnum as usize, window_rows)?;
-
-                    *display_lines.start_mut() = lnum - 1;
-                    *display_lines.end_mut() = lnum - 1;
-                    execute!(stdout(), RestorePosition, MoveTo(0, 0), MoveTo(lcol as u16, 0))?;
-                };
+            let offset = get_scroll_offset(options.page_size(display_rows), repeat);
+            scroll_lines_with_cursor(
+                display_lines,
+                window_rows,
+                lines,
+                search_result,
+                options,
+                cursor_col,
+                now_line_idx,
+                offset,
+            )?;
+        }
+        Event::Key(KeyEvent {
+            code: KeyCode::Char('#'),
+            ..
+        }) => {
+            // toggle the line number gutter, the text width is changed
+            *display_lines.line_numbers_mut() = !display_lines.line_numbers;
+            resize_display_lines(
+                display_lines,
+                window_rows,
+                lines,
+                search_result,
+                options,
+                (now_line_idx as u64, cursor_col),
+            )?;
+        }
+        Event::Key(KeyEvent {
+            code: KeyCode::Char('g') | KeyCode::Home,
+            ..
+        }) => {
+            // jump to the first line, or the count line
+            let line_idx = count.unwrap_or(1).saturating_sub(1);
+            jump_to_line(display_lines, window_rows, lines, search_result, options, line_idx as u64)?;
+        }
+        Event::Key(KeyEvent {
+            code: KeyCode::Char('G') | KeyCode::End,
+            ..
+        }) => {
+            // jump to the last line, or the count line
+            let line_idx = count.unwrap_or(line_count).saturating_sub(1);
+            jump_to_line(display_lines, window_rows, lines, search_result, options, line_idx as u64)?;
+        }
+        Event::Key(KeyEvent {
+            code: KeyCode::Char(c @ ('/' | '?')),
+            ..
+        }) => {
+            let direction = if *c == '/' {
+                SearchDirection::Forward
+            } else {
+                SearchDirection::Backward
             };
-
-            render_search_line(search_result)?;
+            return_search_word_input_mode = true;
+            *search_result.input_direction_mut() = direction;
+            *display_lines.cursor_pos_mut() = (cursor_pos_row as u64, cursor_pos_col as u64);
+            clear_search_line()?;
+            execute!(stdout(), SavePosition, MoveTo(0, window_rows + 1), Print(direction.prompt()))?;
         }
         Event::Key(KeyEvent {
-            code: KeyCode::Char('N'),
+            code: KeyCode::Char(c @ ('n' | 'N')),
             ..
         }) => {
-            // jump previous search result
+            // jump next search result, N is reverse direction of the last search
             if search_result.clone().exists_match() {
-                let now_position_row = now_line_idx as u64 + 2;
-                let now_position_col = display_lines.cursor_pos.1;
-                if let Some((lnum, lcol)) =
-                    search_result.get_near_line_with_previous((now_position_row, now_position_col))
-                {
-                    // jump to result line
-                    execute!(stdout(), RestorePosition, SavePosition, Clear(ClearType::All))?;
-
-                    re_render_display_lines(lines, lnum as usize, window_rows)?;
-
-                    *display_lines.start_mut() = lnum - 1;
-                    *display_lines.end_mut() = lnum - 1;
-                    execute!(stdout(), RestorePosition, MoveTo(0, 0), MoveTo(lcol as u16, 0))?;
+                let direction = if *c == 'n' {
+                    search_result.direction
+                } else {
+                    search_result.direction.reverse()
+                };
+                let col = utils::line::col_to_char(now_line, cursor_col as usize, display_lines.tab_width);
+                let now_position = (now_line_idx as u64 + 1, col as u64);
+                if let Some(pos) = search_result.get_nth_next_match(now_position, direction, repeat) {
+                    jump_to_search_result(display_lines, window_rows, lines, search_result, options, pos)?;
                 };
             };
 
@@ -604,45 +995,339 @@ fn handler_display_input_mode(
     Ok(return_search_word_input_mode)
 }
 
-pub fn less_loop(filename: &str) -> io::Result<()> {
-    let f = File::open(filename)?;
-    let lines = ropey::Rope::from_reader(f)?;
-    let line_count = lines.len_lines() - 1;
-    let mut is_search_word_input_mode = false;
+// append the text of the followed file, the lines from the byte offset are replaced, and update the search matches
+fn append_lines(
+    display_lines: &mut DisplayLines,
+    lines: &mut ropey::Rope,
+    search_result: &mut SearchResult,
+    options: &LessOptions,
+    appended: (u64, String),
+) -> io::Result<()> {
+    let is_hex_view = display_lines.hex_view;
+    let is_styled = options.raw_control_chars && !is_hex_view;
+    let line_idx = input::append_text(lines, display_lines.styles_mut(), appended, is_hex_view, is_styled);
+    display_lines.line_cache.clear();
+    debug!("append: line_idx={:?}, lines={:?}", line_idx, lines.len_lines());
+    search_result.search_appended(lines, line_idx, is_hex_view)?;
+    Ok(())
+}
+
+// lines of the input, the escape sequences are stripped with raw_control_chars
+fn get_lines(input: input::Input, options: &LessOptions) -> (ropey::Rope, Vec<LineStyles>) {
+    if options.raw_control_chars && !input.is_hex_view {
+        // search and cursor columns work on the text without the escape sequences
+        let (text, styles) = utils::ansi::strip(&input.lines.to_string());
+        (ropey::Rope::from_str(&text), styles)
+    } else {
+        (input.lines, Vec::new())
+    }
+}
+
+// load the input again, and search the new lines with the active search. return the version of the loaded file
+fn reload_lines(
+    filename: &str,
+    display_lines: &mut DisplayLines,
+    lines: &mut ropey::Rope,
+    search_result: &mut SearchResult,
+    options: &LessOptions,
+    follower: &mut io::Result<input::Follower>,
+) -> io::Result<Option<input::FileVersion>> {
+    // NOTE: stdin is read until EOF, keep the lines
+    if input::is_stdin(filename) {
+        return Err(io::Error::new(io::ErrorKind::Unsupported, "can't reload standard input"));
+    }
+    let input = input::load(filename, options)?;
+    let version = input.version;
+    *follower = input::Follower::new(filename, &input);
+    *display_lines.hex_view_mut() = input.is_hex_view;
+    let (new_lines, styles) = get_lines(input, options);
+    *lines = new_lines;
+    *display_lines.styles_mut() = styles;
+    display_lines.line_cache.clear();
+    debug!("reload: lines={:?}", lines.len_lines());
+
+    if let Some(matcher) = search_result.matcher.clone() {
+        let matches = if display_lines.hex_view {
+            search::search_hexdump(lines, &search_result.word, &matcher)?
+        } else {
+            search::search(lines, &search_result.word, &matcher)?
+        };
+        search_result.update_matches(1, matches);
+    }
+    Ok(version)
+}
+
+// reload the input, keep the display position and the cursor position in the new lines
+#[allow(clippy::too_many_arguments)]
+fn reload_display_lines(
+    filename: &str,
+    display_lines: &mut DisplayLines,
+    window_rows: u16,
+    lines: &mut ropey::Rope,
+    search_result: &mut SearchResult,
+    options: &LessOptions,
+    follower: &mut io::Result<input::Follower>,
+    (line_idx, col): (u64, u64),
+) -> io::Result<Option<input::FileVersion>> {
+    let version = reload_lines(filename, display_lines, lines, search_result, options, follower)?;
+    let line_idx = line_idx.min(utils::line::get_line_count(lines).saturating_sub(1) as u64);
+    let col = utils::line::get_cursor_col(lines.line(line_idx as usize), col as usize, display_lines.tab_width);
+    resize_display_lines(display_lines, window_rows, lines, search_result, options, (line_idx, col as u64))?;
+    Ok(version)
+}
 
-    let mut search_result = SearchResult::new(filename);
-    let (_, window_rows) = terminal::size()?;
+// show the last page, and move cursor to the last line
+fn follow_end(
+    display_lines: &mut DisplayLines,
+    window_rows: u16,
+    lines: &ropey::Rope,
+    search_result: &SearchResult,
+    options: &LessOptions,
+) -> io::Result<()> {
+    let layout = get_layout(display_lines, lines)?;
+    let display_rows = window_rows as u64 - STATUS_LINE_OFFSET as u64;
+    re_render_display_lines(display_lines, window_rows, lines, search_result, layout.last_top(display_rows))?;
+    let line_idx = utils::line::get_line_count(lines).saturating_sub(1);
+    jump_to_line(display_lines, window_rows, lines, search_result, options, line_idx as u64)?;
+    render_message(FOLLOW_MESSAGE)
+}
+
+// start follow mode, return false when the input can't be followed
+fn start_follow(
+    display_lines: &mut DisplayLines,
+    window_rows: u16,
+    lines: &ropey::Rope,
+    search_result: &SearchResult,
+    options: &LessOptions,
+    follower: &io::Result<input::Follower>,
+) -> io::Result<bool> {
+    match follower {
+        Ok(_) => {
+            follow_end(display_lines, window_rows, lines, search_result, options)?;
+            Ok(true)
+        }
+        Err(e) => {
+            debug!("follow error: {:?}", e);
+            render_message(&e.to_string())?;
+            Ok(false)
+        }
+    }
+}
+
+// rows of the window, keep the rows for the status line and the search line at least
+fn get_window_rows(rows: u16) -> u16 {
+    rows.max(STATUS_LINE_OFFSET as u16 + 1)
+}
+
+// recompute the display lines by the new window size or gutter, and redraw all
+fn resize_display_lines(
+    display_lines: &mut DisplayLines,
+    window_rows: u16,
+    lines: &ropey::Rope,
+    search_result: &SearchResult,
+    options: &LessOptions,
+    (line_idx, col): (u64, u64),
+) -> io::Result<()> {
+    let layout = get_layout(display_lines, lines)?;
+    let display_rows = window_rows as u64 - STATUS_LINE_OFFSET as u64;
+
+    // fill the last page when the window grows, the cursor line is kept in the display by move_cursor_to_col
+    let top = layout
+        .clamp((display_lines.start, display_lines.start_row))
+        .min(layout.last_top(display_rows));
+    debug!("resize: window_rows={:?}, top={:?}", window_rows, top);
+
+    execute!(stdout(), Clear(ClearType::All))?;
+    re_render_display_lines(display_lines, window_rows, lines, search_result, top)?;
+    *display_lines.shadow_cursor_pos_mut() = (line_idx, display_lines.shadow_cursor_pos.1);
+    move_cursor_to_col(display_lines, window_rows, lines, search_result, options, (line_idx, col))?;
+    execute!(stdout(), SavePosition)?;
+
+    Ok(())
+}
+
+pub fn less_loop(filename: &str, input: input::Input, options: &LessOptions) -> io::Result<()> {
+    let display_name = input::get_display_name(filename, input.compression);
+    let mut follower = input::Follower::new(filename, &input);
+    // version of the loaded file, reload when it is changed with --watch
+    let mut file_version = input.version;
+    let is_hex_view = input.is_hex_view;
+    let (mut lines, styles) = get_lines(input, options);
+    let mut line_count = utils::line::get_line_count(&lines);
+    let mut is_search_word_input_mode = false;
+    // show the appended content of the file, stop with Ctrl-C
+    let mut is_follow_mode = false;
+    // notice on the status line, cleared by the next key input
+    let mut notice: Option<&str> = None;
+    // count prefix of the command, e.g. 120g
+    let mut count: Option<usize> = None;
+
+    let mut search_result = SearchResult::new();
+    let mut window_rows = get_window_rows(terminal::size()?.1);
     let mut display_lines = DisplayLines {
         start: 0,
+        start_row: 0,
         end: 0,
         cursor_pos: (0, 0),
         shadow_cursor_pos: (0, 0),
+        h_offset: 0,
+        chop_long_lines: options.chop_long_lines,
+        line_numbers: options.line_numbers,
+        tab_width: options.tab_width(),
+        hex_view: is_hex_view,
+        rows: Vec::new(),
+        styles,
+        line_cache: Rc::default(),
     };
 
-    for idx in 0..(window_rows - STATUS_LINE_OFFSET as u16) {
-        *display_lines.end_mut() = idx as u64;
-        // NOTE: use format, because debug print
-        let disp = format!("{}", lines.line(idx as usize));
-        execute!(stdout(), MoveTo(0, idx), Print(disp))?;
-        if idx >= line_count as u16 - 1 {
-            break;
-        }
-    }
+    re_render_display_lines(&mut display_lines, window_rows, &lines, &search_result, (0, 0))?;
     execute!(stdout(), MoveTo(0, 0), SavePosition)?;
+    if options.follow {
+        is_follow_mode = start_follow(&mut display_lines, window_rows, &lines, &search_result, options, &follower)?;
+    }
 
     loop {
         let (cursor_pos_col, cursor_pos_row) = position()?;
-        let now_line_num = if is_search_word_input_mode {
-            display_lines.start + 1 + display_lines.cursor_pos.0
+        let cursor_pos = if is_search_word_input_mode {
+            display_lines.cursor_pos
         } else {
-            display_lines.start + 1 + cursor_pos_row as u64
+            (cursor_pos_row as u64, cursor_pos_col as u64)
         };
-        let now_line_idx = now_line_num as usize - 1;
+        let (now_line_idx, cursor_col) = get_text_pos(&display_lines, &lines, cursor_pos)?;
+        let now_line_idx = now_line_idx as usize;
 
-        let _ = render_status_line(now_line_num, line_count, cursor_pos_col as u64 + 1, &display_lines, &search_result);
+        let _ = render_status_line(
+            &display_name,
+            now_line_idx as u64 + 1,
+            line_count,
+            cursor_col + 1,
+            &display_lines,
+            &search_result,
+            count,
+            notice,
+        );
+
+        if is_follow_mode && !poll(Duration::from_millis(FOLLOW_INTERVAL_MS))? {
+            // no key input, check the appended content
+            let followed = if follower.as_ref().is_ok_and(|f| f.is_replaced()) {
+                reload_lines(filename, &mut display_lines, &mut lines, &mut search_result, options, &mut follower).map(
+                    |version| {
+                        file_version = version;
+                        notice = Some(FOLLOW_RELOAD_NOTICE);
+                        true
+                    },
+                )
+            } else {
+                follower
+                    .as_mut()
+                    .map_or(Ok(None), |f| f.read())
+                    .and_then(|appended| match appended {
+                        Some(appended) => {
+                            append_lines(&mut display_lines, &mut lines, &mut search_result, options, appended)
+                                .map(|_| true)
+                        }
+                        None => Ok(false),
+                    })
+            };
+            match followed {
+                Ok(true) => {
+                    line_count = utils::line::get_line_count(&lines);
+                    follow_end(&mut display_lines, window_rows, &lines, &search_result, options)?;
+                }
+                Ok(false) => (),
+                Err(e) => {
+                    // NOTE: keep the lines, and retry on the next interval, e.g. the rotated file is not readable yet
+                    debug!("follow error: {:?}", e);
+                    line_count = utils::line::get_line_count(&lines);
+                    render_message(&e.to_string())?;
+                }
+            }
+            continue;
+        }
+
+        let is_watching = options.watch && !is_follow_mode && !is_search_word_input_mode;
+        if is_watching && !poll(Duration::from_millis(FOLLOW_INTERVAL_MS))? {
+            // no key input, reload when the file is changed
+            if input::get_file_version(filename) != file_version {
+                debug!("watch: file changed");
+                match reload_display_lines(
+                    filename,
+                    &mut display_lines,
+                    window_rows,
+                    &mut lines,
+                    &mut search_result,
+                    options,
+                    &mut follower,
+                    (now_line_idx as u64, cursor_col),
+                ) {
+                    Ok(version) => {
+                        file_version = version;
+                        notice = Some(WATCH_RELOAD_NOTICE);
+                    }
+                    Err(e) => {
+                        // NOTE: keep the lines, and retry on the next change
+                        debug!("watch error: {:?}", e);
+                        file_version = input::get_file_version(filename);
+                        render_message(&e.to_string())?;
+                    }
+                }
+                line_count = utils::line::get_line_count(&lines);
+            }
+            continue;
+        }
 
         let event = read()?;
+        if let Event::Key(_) = event {
+            notice = None;
+        }
+
+        if let Event::Resize(_, rows) = event {
+            window_rows = get_window_rows(rows);
+            resize_display_lines(
+                &mut display_lines,
+                window_rows,
+                &lines,
+                &search_result,
+                options,
+                (now_line_idx as u64, cursor_col),
+            )?;
+            if is_search_word_input_mode {
+                // restore the search word input
+                let (cursor_pos_col, cursor_pos_row) = position()?;
+                *display_lines.cursor_pos_mut() = (cursor_pos_row as u64, cursor_pos_col as u64);
+                let prompt = format!(
+                    "{}{}",
+                    search_result.input_direction.prompt(),
+                    String::from_iter(search_result.word_vec.clone())
+                );
+                execute!(stdout(), MoveTo(0, window_rows - 1), Clear(ClearType::UntilNewLine), Print(prompt))?;
+            }
+            if is_follow_mode {
+                follow_end(&mut display_lines, window_rows, &lines, &search_result, options)?;
+            }
+            continue;
+        }
 
+        if is_follow_mode {
+            match event {
+                Event::Key(KeyEvent {
+                    code: KeyCode::Char('c'),
+                    modifiers: KeyModifiers::CONTROL,
+                    ..
+                }) => {
+                    debug!("stop follow");
+                    is_follow_mode = false;
+                    render_search_line(&search_result)?;
+                }
+                Event::Key(KeyEvent { code: KeyCode::Esc, .. }) => {
+                    debug!("exit");
+                    break;
+                }
+                // NOTE: the other keys are ignored while following, same as less
+                _ => (),
+            }
+            continue;
+        }
         let _ = clear_status_line();
 
         if is_search_word_input_mode {
@@ -653,6 +1338,7 @@ pub fn less_loop(filename: &str) -> io::Result<()> {
                 &event,
                 is_search_word_input_mode,
                 &mut search_result,
+                options,
             )?;
         } else {
             let _ = render_search_line(&search_result);
@@ -660,10 +1346,62 @@ pub fn less_loop(filename: &str) -> io::Result<()> {
             execute!(stdout(), SavePosition)?;
 
             if let Event::Key(KeyEvent { code: KeyCode::Esc, .. }) = event {
+                if count.take().is_some() {
+                    // cancel count prefix
+                    continue;
+                }
                 debug!("exit");
                 break;
             }
 
+            if let Event::Key(KeyEvent {
+                code: KeyCode::Char(c @ '0'..='9'),
+                ..
+            }) = event
+            {
+                let digit = c.to_digit(10).unwrap_or(0) as usize;
+                count = Some(count.unwrap_or(0).saturating_mul(10).saturating_add(digit));
+                continue;
+            }
+
+            if let Event::Key(KeyEvent {
+                code: KeyCode::Char('F'),
+                ..
+            }) = event
+            {
+                count = None;
+                is_follow_mode =
+                    start_follow(&mut display_lines, window_rows, &lines, &search_result, options, &follower)?;
+                continue;
+            }
+
+            if let Event::Key(KeyEvent {
+                code: KeyCode::Char('R'),
+                ..
+            }) = event
+            {
+                // reload the file, keep the position and the search
+                count = None;
+                match reload_display_lines(
+                    filename,
+                    &mut display_lines,
+                    window_rows,
+                    &mut lines,
+                    &mut search_result,
+                    options,
+                    &mut follower,
+                    (now_line_idx as u64, cursor_col),
+                ) {
+                    Ok(version) => file_version = version,
+                    Err(e) => {
+                        debug!("reload error: {:?}", e);
+                        render_message(&e.to_string())?;
+                    }
+                }
+                line_count = utils::line::get_line_count(&lines);
+                continue;
+            }
+
             is_search_word_input_mode = handler_display_input_mode(
                 &mut display_lines,
                 window_rows,
@@ -675,9 +1413,31 @@ pub fn less_loop(filename: &str) -> io::Result<()> {
                 &event,
                 is_search_word_input_mode,
                 &mut search_result,
+                count.take(),
+                options,
             )?;
         }
     }
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_get_correction_cursor_col() {
+        assert_eq!(get_correction_cursor_col(3, 3, 10), None);
+        // restore the shadow cursor column in the long enough line
+        assert_eq!(get_correction_cursor_col(2, 5, 10), Some(5));
+        assert_eq!(get_correction_cursor_col(5, 0, 10), Some(0));
+        // the last column of the short line
+        assert_eq!(get_correction_cursor_col(4, 70000, 5), Some(4));
+        assert_eq!(get_correction_cursor_col(0, 3, 1), Some(0));
+        assert_eq!(get_correction_cursor_col(0, 3, 0), Some(0));
+        // the columns over u16
+        assert_eq!(get_correction_cursor_col(4, 70000, 100000), Some(70000));
+        assert_eq!(get_correction_cursor_col(4, 70000, 65536), Some(65535));
+    }
+}
@@ -1,27 +1,56 @@
 use grep::matcher::Matcher;
 use grep::regex::RegexMatcher;
-use grep::searcher::sinks::UTF8;
-use grep::searcher::SearcherBuilder;
 use log::debug;
+use std::borrow::Cow;
 use std::io;
 
+use crate::utils;
+use crate::utils::hexdump;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SearchDirection {
+    Forward,
+    Backward,
+}
+
+impl SearchDirection {
+    pub fn reverse(self) -> SearchDirection {
+        match self {
+            SearchDirection::Forward => SearchDirection::Backward,
+            SearchDirection::Backward => SearchDirection::Forward,
+        }
+    }
+
+    // prompt character of the search line
+    pub fn prompt(self) -> char {
+        match self {
+            SearchDirection::Forward => '/',
+            SearchDirection::Backward => '?',
+        }
+    }
+}
+
 #[derive(Clone, Debug)]
-pub struct SearchResult<'a> {
-    pub filename: &'a str,
+pub struct SearchResult {
     pub word: String,
-    pub word_vec: Vec<char>,          // input temporary search word
-    pub match_lines: Vec<(u64, u64)>, // (line number, position)
+    pub word_vec: Vec<char>,               // input temporary search word
+    pub match_lines: Vec<(u64, u64, u64)>, // (line number, position, length)
     pub now_idx: Option<usize>,
+    pub matcher: Option<RegexMatcher>,    // active search pattern, use for highlight
+    pub direction: SearchDirection,       // direction of the last search, use with n/N
+    pub input_direction: SearchDirection, // direction of the search word in input
 }
 
-impl SearchResult<'_> {
-    pub fn new(filename: &'_ str) -> SearchResult<'_> {
+impl SearchResult {
+    pub fn new() -> SearchResult {
         SearchResult {
-            filename,
             word: String::new(),
             word_vec: Vec::new(),
             match_lines: Vec::new(),
             now_idx: None,
+            matcher: None,
+            direction: SearchDirection::Forward,
+            input_direction: SearchDirection::Forward,
         }
     }
     pub fn word_mut(&mut self) -> &mut String {
@@ -30,89 +59,371 @@ impl SearchResult<'_> {
     pub fn word_vec_mut(&mut self) -> &mut Vec<char> {
         &mut self.word_vec
     }
-    pub fn match_lines_mut(&mut self) -> &mut Vec<(u64, u64)> {
+    pub fn match_lines_mut(&mut self) -> &mut Vec<(u64, u64, u64)> {
         &mut self.match_lines
     }
+    pub fn matcher_mut(&mut self) -> &mut Option<RegexMatcher> {
+        &mut self.matcher
+    }
+    pub fn direction_mut(&mut self) -> &mut SearchDirection {
+        &mut self.direction
+    }
+    pub fn input_direction_mut(&mut self) -> &mut SearchDirection {
+        &mut self.input_direction
+    }
     pub fn exists_match(self) -> bool {
         self.now_idx.is_some()
     }
 
+    // get the first match at or after now_pos (line number, position)
     pub fn get_near_line(&mut self, now_pos: (u64, u64)) -> Option<(u64, u64)> {
         let mut pos = None;
-        for idx in 0..self.match_lines.clone().len() {
-            let (line_num, _) = self.match_lines[idx];
-            if line_num >= now_pos.0 {
-                pos = Some(self.match_lines[idx]);
+        for idx in 0..self.match_lines.len() {
+            let (line_num, col, _) = self.match_lines[idx];
+            if (line_num, col) >= now_pos {
+                pos = Some((line_num, col));
                 self.now_idx = Some(idx);
                 break;
             }
         }
         if pos.is_none() {
             // if not found, search from the beginning
-            for idx in 0..self.match_lines.clone().len() {
-                let (line_num, _) = self.match_lines[idx];
-                if line_num < now_pos.0 {
-                    pos = Some(self.match_lines[idx]);
-                    self.now_idx = Some(idx);
-                    break;
-                }
-                if line_num >= now_pos.0 {
-                    break;
-                }
+            if let Some(&(line_num, col, _)) = self.match_lines.first() {
+                pos = Some((line_num, col));
+                self.now_idx = Some(0);
             }
         }
         pos
     }
 
+    // get the last match before now_pos (line number, position)
     pub fn get_near_line_with_previous(&mut self, now_pos: (u64, u64)) -> Option<(u64, u64)> {
         let mut pos = None;
-        for idx in (0..self.match_lines.clone().len()).rev() {
-            let (line_num, _) = self.match_lines[idx];
-            if line_num + 1 < now_pos.0 {
-                pos = Some(self.match_lines[idx]);
+        for idx in (0..self.match_lines.len()).rev() {
+            let (line_num, col, _) = self.match_lines[idx];
+            if (line_num, col) < now_pos {
+                pos = Some((line_num, col));
                 self.now_idx = Some(idx);
                 break;
             }
         }
         if pos.is_none() {
             // if not found, search from the end
-            for idx in (0..self.match_lines.clone().len()).rev() {
-                let (line_num, _) = self.match_lines[idx];
-                if line_num + 1 >= now_pos.0 {
-                    pos = Some(self.match_lines[idx]);
-                    self.now_idx = Some(idx);
-                    break;
-                }
-                if line_num + 1 < now_pos.0 {
-                    break;
-                }
+            if let Some(&(line_num, col, _)) = self.match_lines.last() {
+                pos = Some((line_num, col));
+                self.now_idx = Some(self.match_lines.len() - 1);
             }
         }
         pos
     }
 
+    // get the next match from now_pos for n/N, not including now_pos
+    pub fn get_next_match(&mut self, now_pos: (u64, u64), direction: SearchDirection) -> Option<(u64, u64)> {
+        match direction {
+            SearchDirection::Forward => self.get_near_line((now_pos.0, now_pos.1 + 1)),
+            SearchDirection::Backward => self.get_near_line_with_previous(now_pos),
+        }
+    }
+
+    // get the count-th next match from now_pos, step by the index not to scan the matches for each count
+    pub fn get_nth_next_match(
+        &mut self,
+        now_pos: (u64, u64),
+        direction: SearchDirection,
+        count: usize,
+    ) -> Option<(u64, u64)> {
+        self.get_next_match(now_pos, direction)?;
+        let idx = self.now_idx?;
+        let len = self.match_lines.len();
+        // NOTE: the matches wrap around
+        let steps = count.saturating_sub(1) % len;
+        let idx = match direction {
+            SearchDirection::Forward => (idx + steps) % len,
+            SearchDirection::Backward => (idx + len - steps) % len,
+        };
+        self.now_idx = Some(idx);
+        let (line_num, col, _) = self.match_lines[idx];
+        Some((line_num, col))
+    }
+
     pub fn reset(&mut self) {
         self.word = String::new();
         self.word_vec = Vec::new();
         self.match_lines = Vec::new();
         self.now_idx = None;
+        self.matcher = None;
+        self.direction = SearchDirection::Forward;
     }
+
+    // replace the matches from the line number, use with the appended lines
+    pub fn update_matches(&mut self, line_num: u64, matches: Vec<(u64, u64, u64)>) {
+        let first = self.match_lines.partition_point(|m| m.0 < line_num);
+        self.match_lines.truncate(first);
+        self.match_lines.extend(matches);
+        self.now_idx = match self.match_lines.len() {
+            0 => None,
+            len => Some(self.now_idx.unwrap_or(0).min(len - 1)),
+        };
+    }
+
+    // search the appended lines from the line index again with the active pattern
+    pub fn search_appended(&mut self, lines: &ropey::Rope, line_idx: usize, is_hex_view: bool) -> io::Result<()> {
+        let Some(matcher) = self.matcher.clone() else {
+            return Ok(());
+        };
+        // NOTE: the hex byte pattern can match across the lines, search all again
+        let (line_idx, matches) = if is_hex_view {
+            (0, search_hexdump(lines, &self.word, &matcher)?)
+        } else {
+            (line_idx, search_from(lines, &self.word, &matcher, line_idx)?)
+        };
+        self.update_matches(line_idx as u64 + 1, matches);
+        Ok(())
+    }
+
+    // (start, end) char ranges of the matches in the line, the empty matches are not highlighted
+    pub fn line_match_ranges(&self, line_num: u64) -> Vec<(usize, usize)> {
+        let first = self.match_lines.partition_point(|m| m.0 < line_num);
+        self.match_lines[first..]
+            .iter()
+            .take_while(|m| m.0 == line_num)
+            .filter(|m| m.2 > 0)
+            .map(|&(_, col, len)| (col as usize, (col + len) as usize))
+            .collect()
+    }
+}
+
+pub fn build_matcher(search_word: &str) -> io::Result<RegexMatcher> {
+    RegexMatcher::new(search_word).map_err(|e| {
+        // NOTE: regex error message is multi lines, use the last line (e.g. "error: unclosed group")
+        let message = e.to_string();
+        let message = message
+            .lines()
+            .rev()
+            .find(|l| !l.trim().is_empty())
+            .unwrap_or("invalid pattern");
+        io::Error::new(io::ErrorKind::InvalidInput, message.trim().to_string())
+    })
 }
 
-pub fn search(filename: &str, search_word: &str) -> io::Result<Vec<(u64, u64)>> {
-    debug!("start search: search_word={}", search_word);
-    let matcher = RegexMatcher::new(search_word).unwrap();
-    let mut matches: Vec<(u64, u64)> = vec![];
-    let mut searcher = SearcherBuilder::new().build();
-    searcher.search_path(
-        &matcher,
-        filename,
-        UTF8(|lnum, _line| {
-            let linematch = matcher.find_at(_line.as_bytes(), 0).unwrap();
-            matches.push((lnum, linematch.unwrap().start() as u64));
-            Ok(true)
-        }),
-    )?;
+fn find_line_matches(matcher: &RegexMatcher, line: ropey::RopeSlice) -> io::Result<Vec<(usize, usize)>> {
+    let text: Cow<str> = line.into();
+    let text = text.trim_end_matches(['\n', '\r']);
+    // NOTE: search the original bytes, the invalid bytes of UTF-8 are restored
+    let (bytes, offsets) = utils::bytes::encode_original(text);
+    let mut ranges = vec![];
+    matcher
+        .find_iter(&bytes, |m| {
+            // the match in the middle of the char includes the whole char.
+            // NOTE: keep the empty match too, e.g. "^$" for the blank lines
+            let start = offsets.partition_point(|o| *o <= m.start()) - 1;
+            let end = offsets.partition_point(|o| *o < m.end()).max(start);
+            ranges.push((start, end));
+            true
+        })
+        .map_err(|e| io::Error::other(e.to_string()))?;
+    Ok(ranges)
+}
+
+pub fn search(lines: &ropey::Rope, search_word: &str, matcher: &RegexMatcher) -> io::Result<Vec<(u64, u64, u64)>> {
+    search_from(lines, search_word, matcher, 0)
+}
+
+// search the lines from the line index, use with the appended lines
+pub fn search_from(
+    lines: &ropey::Rope,
+    search_word: &str,
+    matcher: &RegexMatcher,
+    from_line_idx: usize,
+) -> io::Result<Vec<(u64, u64, u64)>> {
+    debug!("start search: search_word={}, from_line_idx={}", search_word, from_line_idx);
+    let mut matches: Vec<(u64, u64, u64)> = vec![];
+    // NOTE: use rope line index directly, so line numbers are consistent with the display.
+    // not to match the empty line after the last line break
+    let line_count = utils::line::get_line_count(lines).saturating_sub(from_line_idx);
+    for (idx, line) in lines.lines_at(from_line_idx).take(line_count).enumerate() {
+        for (start, end) in find_line_matches(matcher, line)? {
+            matches.push(((from_line_idx + idx) as u64 + 1, start as u64, (end - start) as u64));
+        }
+    }
     debug!("start end: search_word={}, hit={}", search_word, matches.len());
     Ok(matches)
 }
+
+// search the bytes of the hex dump view by the pattern, and the hex byte pattern too (e.g. "7f 45 4c 46").
+// the matches are the hex columns of each line
+pub fn search_hexdump(
+    lines: &ropey::Rope,
+    search_word: &str,
+    matcher: &RegexMatcher,
+) -> io::Result<Vec<(u64, u64, u64)>> {
+    debug!("start hex search: search_word={}", search_word);
+    let bytes: Vec<u8> = lines
+        .lines()
+        .flat_map(|line| hexdump::parse_line(&Cow::from(line)))
+        .collect();
+
+    let mut ranges = vec![];
+    matcher
+        .find_iter(&bytes, |m| {
+            if !m.is_empty() {
+                ranges.push((m.start(), m.end()));
+            }
+            true
+        })
+        .map_err(|e| io::Error::other(e.to_string()))?;
+    if let Some(pattern) = hexdump::parse_hex_pattern(search_word) {
+        for (start, window) in bytes.windows(pattern.len()).enumerate() {
+            if window == pattern.as_slice() {
+                ranges.push((start, start + pattern.len()));
+            }
+        }
+    }
+
+    // split the byte ranges into the lines
+    let mut matches: Vec<(u64, u64, u64)> = vec![];
+    for (start, end) in ranges {
+        let mut offset = start;
+        while offset < end {
+            let line_idx = offset / hexdump::LINE_BYTES;
+            let line_end = end.min((line_idx + 1) * hexdump::LINE_BYTES);
+            let line_offset = line_idx * hexdump::LINE_BYTES;
+            let col = hexdump::hex_col(line_offset, offset % hexdump::LINE_BYTES);
+            let end_col = hexdump::hex_col(line_offset, (line_end - 1) % hexdump::LINE_BYTES) + 2;
+            matches.push((line_idx as u64 + 1, col as u64, (end_col - col) as u64));
+            offset = line_end;
+        }
+    }
+    matches.sort_unstable();
+    matches.dedup_by_key(|m| (m.0, m.1));
+    debug!("end hex search: search_word={}, hit={}", search_word, matches.len());
+    Ok(matches)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn search_result(match_lines: Vec<(u64, u64, u64)>) -> SearchResult {
+        let mut search_result = SearchResult::new();
+        *search_result.match_lines_mut() = match_lines;
+        search_result
+    }
+
+    #[test]
+    fn test_get_next_match() {
+        let mut sr = search_result(vec![(1, 2, 1), (3, 0, 1), (3, 4, 1)]);
+        assert_eq!(sr.get_next_match((1, 2), SearchDirection::Forward), Some((3, 0)));
+        assert_eq!(sr.get_next_match((3, 0), SearchDirection::Forward), Some((3, 4)));
+        assert_eq!(sr.now_idx, Some(2));
+        assert_eq!(sr.get_next_match((3, 4), SearchDirection::Backward), Some((3, 0)));
+        assert_eq!(sr.get_next_match((3, 0), SearchDirection::Backward), Some((1, 2)));
+    }
+
+    #[test]
+    fn test_get_next_match_wraparound() {
+        let mut sr = search_result(vec![(1, 2, 1), (3, 0, 1)]);
+        assert_eq!(sr.get_next_match((3, 0), SearchDirection::Forward), Some((1, 2)));
+        assert_eq!(sr.now_idx, Some(0));
+        assert_eq!(sr.get_next_match((1, 2), SearchDirection::Backward), Some((3, 0)));
+        assert_eq!(sr.now_idx, Some(1));
+
+        let mut sr = search_result(vec![]);
+        assert_eq!(sr.get_next_match((1, 0), SearchDirection::Forward), None);
+        assert_eq!(sr.now_idx, None);
+    }
+
+    #[test]
+    fn test_get_nth_next_match() {
+        let mut sr = search_result(vec![(1, 2, 1), (3, 0, 1), (3, 4, 1)]);
+        assert_eq!(sr.get_nth_next_match((1, 0), SearchDirection::Forward, 1), Some((1, 2)));
+        assert_eq!(sr.get_nth_next_match((1, 0), SearchDirection::Forward, 3), Some((3, 4)));
+        assert_eq!(sr.get_nth_next_match((1, 0), SearchDirection::Forward, 4), Some((1, 2)));
+        assert_eq!(sr.get_nth_next_match((3, 4), SearchDirection::Backward, 2), Some((1, 2)));
+        assert_eq!(sr.get_nth_next_match((3, 4), SearchDirection::Backward, 3), Some((3, 4)));
+        // the huge count doesn't scan the matches for each count
+        assert_eq!(sr.get_nth_next_match((1, 0), SearchDirection::Forward, usize::MAX), Some((3, 4)));
+        assert_eq!(sr.now_idx, Some(2));
+
+        let mut sr = search_result(vec![]);
+        assert_eq!(sr.get_nth_next_match((1, 0), SearchDirection::Forward, 5), None);
+    }
+
+    #[test]
+    fn test_line_match_ranges() {
+        let sr = search_result(vec![(1, 2, 1), (3, 0, 2), (3, 3, 0), (3, 4, 1)]);
+        assert_eq!(sr.line_match_ranges(3), vec![(0, 2), (4, 5)]);
+        assert!(sr.line_match_ranges(2).is_empty());
+    }
+
+    #[test]
+    fn test_update_matches() {
+        let mut sr = search_result(vec![(1, 2, 1), (3, 0, 1), (3, 4, 1)]);
+        sr.now_idx = Some(2);
+        sr.update_matches(3, vec![(3, 1, 1)]);
+        assert_eq!(sr.match_lines, vec![(1, 2, 1), (3, 1, 1)]);
+        assert_eq!(sr.now_idx, Some(1));
+        sr.update_matches(1, vec![]);
+        assert_eq!(sr.now_idx, None);
+    }
+
+    #[test]
+    fn test_search_appended() {
+        let mut sr = SearchResult::new();
+        let mut lines = ropey::Rope::from_str("foo\nba");
+        *sr.word_mut() = String::from("bar");
+        *sr.matcher_mut() = Some(build_matcher("bar").unwrap());
+        sr.search_appended(&lines, 0, false).unwrap();
+        assert!(sr.match_lines.is_empty());
+
+        // the replaced partial line is searched again
+        lines.insert(lines.len_chars(), "r bar\n");
+        sr.search_appended(&lines, 1, false).unwrap();
+        assert_eq!(sr.match_lines, vec![(2, 0, 3), (2, 4, 3)]);
+        assert_eq!(sr.now_idx, Some(0));
+    }
+
+    #[test]
+    fn test_search() {
+        let lines = ropey::Rope::from_str("foo bar foo\r\nbaz\n\u{3042}foo\n");
+        let matcher = build_matcher("foo").unwrap();
+        assert_eq!(search(&lines, "foo", &matcher).unwrap(), vec![(1, 0, 3), (1, 8, 3), (3, 1, 3)]);
+        assert_eq!(search_from(&lines, "foo", &matcher, 2).unwrap(), vec![(3, 1, 3)]);
+
+        // the empty matches of the blank lines, not the empty line after the last line break
+        let lines = ropey::Rope::from_str("a\n\nb\r\n\r\n");
+        let matcher = build_matcher("^$").unwrap();
+        assert_eq!(search(&lines, "^$", &matcher).unwrap(), vec![(2, 0, 0), (4, 0, 0)]);
+        let matcher = build_matcher("b*").unwrap();
+        assert_eq!(
+            search(&lines, "b*", &matcher).unwrap(),
+            vec![(1, 0, 0), (1, 1, 0), (2, 0, 0), (3, 0, 1), (4, 0, 0)]
+        );
+    }
+
+    #[test]
+    fn test_search_hexdump() {
+        let mut bytes = vec![0u8; 14];
+        bytes.extend(b"ELF!");
+        let lines = ropey::Rope::from_str(&hexdump::dump(&bytes, 0));
+        let matcher = build_matcher("ELF").unwrap();
+        // the match across the lines is split into the lines
+        assert_eq!(
+            search_hexdump(&lines, "ELF", &matcher).unwrap(),
+            vec![
+                (1, hexdump::hex_col(0, 14) as u64, 5),
+                (2, hexdump::hex_col(16, 0) as u64, 2)
+            ]
+        );
+        // the hex byte pattern
+        let matcher = build_matcher("46 21").unwrap();
+        assert_eq!(search_hexdump(&lines, "46 21", &matcher).unwrap(), vec![(2, hexdump::hex_col(16, 0) as u64, 5)]);
+    }
+
+    #[test]
+    fn test_build_matcher_error() {
+        let e = build_matcher("(foo").unwrap_err();
+        assert_eq!(e.kind(), io::ErrorKind::InvalidInput);
+        assert!(!e.to_string().contains('\n'));
+    }
+}
@@ -0,0 +1,190 @@
+const ESC: char = '\x1b';
+const BEL: char = '\x07';
+
+// SGR sequences of the line, (char index in the stripped line, escape sequence)
+pub type LineStyles = Vec<(usize, String)>;
+
+// the SGR sequence resets all attributes, e.g. "\x1b[m", "\x1b[0;31m"
+fn has_reset(sequence: &str) -> bool {
+    let mut params = sequence[2..sequence.len() - 1].split(';');
+    while let Some(param) = params.next() {
+        match param {
+            // NOTE: skip the color index of 256 colors and the RGB values, they may be 0
+            "38" | "48" | "58" => match params.next() {
+                Some("5") => {
+                    params.next();
+                }
+                Some("2") => {
+                    params.nth(2);
+                }
+                _ => (),
+            },
+            _ if param.is_empty() || param.parse::<u32>() == Ok(0) => return true,
+            _ => (),
+        }
+    }
+    false
+}
+
+// add the SGR sequence to the active sequences since the last reset
+fn push_state(state: &mut Vec<String>, sequence: &str) {
+    if has_reset(sequence) {
+        state.clear();
+    }
+    // NOTE: keep the last one of the same sequences, the state doesn't grow by the repeated colors
+    state.retain(|s| s != sequence);
+    if sequence != "\x1b[m" && sequence != "\x1b[0m" {
+        state.push(sequence.to_string());
+    }
+}
+
+// active SGR sequences at the end of the line, use with strip_with_state for the next line
+pub fn get_end_state(line_styles: &LineStyles) -> Vec<String> {
+    let mut state = vec![];
+    for (_, sequence) in line_styles {
+        push_state(&mut state, sequence);
+    }
+    state
+}
+
+// strip the escape sequences from the text, and keep the SGR sequences of each line for rendering
+pub fn strip(text: &str) -> (String, Vec<LineStyles>) {
+    strip_with_state(text, vec![])
+}
+
+// strip the text after the lines with the active SGR sequences.
+// NOTE: the active sequences are carried over the line breaks, they are at the char 0 of each line
+pub fn strip_with_state(text: &str, mut state: Vec<String>) -> (String, Vec<LineStyles>) {
+    let mut stripped = String::with_capacity(text.len());
+    let mut styles = vec![];
+    let mut line_styles: LineStyles = state.iter().map(|s| (0, s.clone())).collect();
+    // char index in the stripped line
+    let mut col = 0;
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c == '\n' {
+            stripped.push(c);
+            styles.push(std::mem::take(&mut line_styles));
+            line_styles = state.iter().map(|s| (0, s.clone())).collect();
+            col = 0;
+            continue;
+        }
+        if c != ESC {
+            stripped.push(c);
+            col += 1;
+            continue;
+        }
+        // NOTE: the line break is not a part of the escape sequence, the broken sequence is dropped at the line end
+        match chars.next_if(|c| *c != '\n') {
+            Some('[') => {
+                // CSI, parameter and intermediate bytes until the final byte
+                let mut sequence = String::from("\x1b[");
+                let mut is_terminated = false;
+                while let Some(c) = chars.next_if(|c| ('\x20'..='\x7e').contains(c)) {
+                    sequence.push(c);
+                    if ('\x40'..='\x7e').contains(&c) {
+                        is_terminated = true;
+                        break;
+                    }
+                }
+                // NOTE: only keep the SGR, the other sequences move the cursor
+                if is_terminated && sequence.ends_with('m') {
+                    push_state(&mut state, &sequence);
+                    line_styles.push((col, sequence));
+                }
+            }
+            Some(']') => {
+                // OSC, until BEL or ST
+                while let Some(c) = chars.next_if(|c| *c != '\n') {
+                    if c == BEL || (c == ESC && chars.next_if_eq(&'\\').is_some()) {
+                        break;
+                    }
+                }
+            }
+            // other escape sequences are two chars
+            _ => (),
+        }
+    }
+    styles.push(line_styles);
+
+    (stripped, styles)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_strip() {
+        let (text, styles) = strip("a\x1b[31mred\x1b[0m\x1b[2K\n\x1b]0;title\x07b\n");
+        assert_eq!(text, "ared\nb\n");
+        assert_eq!(
+            styles,
+            vec![
+                vec![(1, "\x1b[31m".to_string()), (4, "\x1b[0m".to_string())],
+                vec![],
+                vec![]
+            ]
+        );
+    }
+
+    #[test]
+    fn test_strip_broken_sequences() {
+        // the lone ESC and the unterminated CSI don't consume the line break
+        let (text, styles) = strip("a\x1b\nb\nc\x1b[31\nd\n");
+        assert_eq!(text, "a\nb\nc\nd\n");
+        assert!(styles.iter().all(|line_styles| line_styles.is_empty()));
+        // the CSI stops at the byte out of the sequence
+        let (text, _) = strip("\x1b[3\u{3042}x\n");
+        assert_eq!(text, "\u{3042}x\n");
+        // the unterminated OSC stops at the line end
+        let (text, _) = strip("\x1b]0;title\nnext\n");
+        assert_eq!(text, "\nnext\n");
+    }
+
+    #[test]
+    fn test_strip_carry_over_lines() {
+        let (text, styles) = strip("\x1b[31mred\nstill\x1b[0m\n");
+        assert_eq!(text, "red\nstill\n");
+        assert_eq!(
+            styles,
+            vec![
+                vec![(0, "\x1b[31m".to_string())],
+                vec![(0, "\x1b[31m".to_string()), (5, "\x1b[0m".to_string())],
+                vec![],
+            ]
+        );
+    }
+
+    #[test]
+    fn test_strip_with_state() {
+        let state = get_end_state(&vec![(0, "\x1b[1m".to_string()), (2, "\x1b[32m".to_string())]);
+        assert_eq!(state, vec!["\x1b[1m".to_string(), "\x1b[32m".to_string()]);
+        let (_, styles) = strip_with_state("a\x1b[0;33mb\n", state);
+        assert_eq!(
+            styles[0],
+            vec![
+                (0, "\x1b[1m".to_string()),
+                (0, "\x1b[32m".to_string()),
+                (1, "\x1b[0;33m".to_string())
+            ]
+        );
+        assert_eq!(styles[1], vec![(0, "\x1b[0;33m".to_string())]);
+    }
+
+    #[test]
+    fn test_has_reset() {
+        assert!(has_reset("\x1b[m"));
+        assert!(has_reset("\x1b[0m"));
+        assert!(has_reset("\x1b[1;0;31m"));
+        assert!(!has_reset("\x1b[31m"));
+        assert!(!has_reset("\x1b[38;5;0m"));
+        assert!(!has_reset("\x1b[48;2;0;0;0m"));
+    }
+
+    #[test]
+    fn test_state_does_not_grow() {
+        let (_, styles) = strip("\x1b[31ma\n\x1b[32mb\n\x1b[31mc\n\x1b[32md\n");
+        assert_eq!(styles[4], vec![(0, "\x1b[31m".to_string()), (0, "\x1b[32m".to_string())]);
+    }
+}
@@ -0,0 +1,89 @@
+// NOTE: the rope is UTF-8 only, the invalid bytes are kept as the chars of the private use area
+const INVALID_BYTE_BASE: u32 = 0x10ff00;
+
+// char keeping the byte
+fn byte_char(b: u8) -> char {
+    char::from_u32(INVALID_BYTE_BASE + b as u32).unwrap_or(char::REPLACEMENT_CHARACTER)
+}
+
+// decode the bytes as UTF-8, and replace each invalid byte with the char keeping the byte.
+// NOTE: the valid chars in the range of the invalid bytes are replaced with the chars of their bytes too,
+// so encode_original restores the original bytes
+pub fn decode_lossy(bytes: &[u8]) -> String {
+    let mut text = String::with_capacity(bytes.len());
+    for chunk in bytes.utf8_chunks() {
+        let valid = chunk.valid();
+        // the first byte of the chars in the range is 0xf4
+        if valid.as_bytes().contains(&0xf4) {
+            for c in valid.chars() {
+                if invalid_byte(c).is_some() {
+                    text.extend(c.encode_utf8(&mut [0; 4]).bytes().map(byte_char));
+                } else {
+                    text.push(c);
+                }
+            }
+        } else {
+            text.push_str(valid);
+        }
+        text.extend(chunk.invalid().iter().map(|b| byte_char(*b)));
+    }
+    text
+}
+
+// original byte of the char, when the char is replaced from the invalid byte
+pub fn invalid_byte(c: char) -> Option<u8> {
+    let code = c as u32;
+    if (INVALID_BYTE_BASE + 0x80..=INVALID_BYTE_BASE + 0xff).contains(&code) {
+        Some((code - INVALID_BYTE_BASE) as u8)
+    } else {
+        None
+    }
+}
+
+// original bytes of the text, and the byte offset of each char with the end offset
+pub fn encode_original(text: &str) -> (Vec<u8>, Vec<usize>) {
+    let mut bytes = Vec::with_capacity(text.len());
+    let mut offsets = Vec::with_capacity(text.len() + 1);
+    for c in text.chars() {
+        offsets.push(bytes.len());
+        match invalid_byte(c) {
+            Some(b) => bytes.push(b),
+            None => bytes.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes()),
+        }
+    }
+    offsets.push(bytes.len());
+    (bytes, offsets)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn round_trip(bytes: &[u8]) -> Vec<u8> {
+        encode_original(&decode_lossy(bytes)).0
+    }
+
+    #[test]
+    fn test_decode_lossy() {
+        let text = decode_lossy(b"a\xffb\xe3\x81");
+        assert_eq!(text.chars().count(), 5);
+        assert_eq!(invalid_byte(text.chars().nth(1).unwrap()), Some(0xff));
+        assert_eq!(invalid_byte('a'), None);
+        assert_eq!(decode_lossy("\u{3042}".as_bytes()), "\u{3042}");
+    }
+
+    #[test]
+    fn test_round_trip() {
+        assert_eq!(round_trip(b"a\xffb\xe3\x81"), b"a\xffb\xe3\x81");
+        // the valid chars in the range of the invalid bytes
+        let bytes = "a\u{10FF90}b\u{10FFFF}\u{10FF7F}".as_bytes();
+        assert_eq!(round_trip(bytes), bytes);
+    }
+
+    #[test]
+    fn test_encode_original_offsets() {
+        let (bytes, offsets) = encode_original(&decode_lossy(b"a\xff\xe3\x81\x82"));
+        assert_eq!(bytes, b"a\xff\xe3\x81\x82");
+        assert_eq!(offsets, vec![0, 1, 2, 5]);
+    }
+}
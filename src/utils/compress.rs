@@ -0,0 +1,164 @@
+use std::io;
+use std::io::Read;
+
+// compression format of the input, detected by the magic number
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Compression {
+    Gzip,
+    Zstd,
+    Bzip2,
+    Xz,
+}
+
+impl Compression {
+    pub fn name(self) -> &'static str {
+        match self {
+            Compression::Gzip => "gzip",
+            Compression::Zstd => "zstd",
+            Compression::Bzip2 => "bzip2",
+            Compression::Xz => "xz",
+        }
+    }
+}
+
+// "BZh", the block size '1'..'9', and the block magic of pi, or the end of stream magic of the empty stream
+fn is_bzip2(bytes: &[u8]) -> bool {
+    bytes.len() >= 10
+        && bytes.starts_with(b"BZh")
+        && (b'1'..=b'9').contains(&bytes[3])
+        && (bytes[4..10] == [0x31, 0x41, 0x59, 0x26, 0x53, 0x59]
+            || bytes[4..10] == [0x17, 0x72, 0x45, 0x38, 0x50, 0x90])
+}
+
+// NOTE: the text can start with the magic number, the caller should fall back to the bytes on the decompress error
+pub fn detect(bytes: &[u8]) -> Option<Compression> {
+    // the compression method of gzip is always deflate
+    if bytes.starts_with(&[0x1f, 0x8b, 0x08]) {
+        Some(Compression::Gzip)
+    } else if bytes.starts_with(&[0x28, 0xb5, 0x2f, 0xfd]) {
+        Some(Compression::Zstd)
+    } else if is_bzip2(bytes) {
+        Some(Compression::Bzip2)
+    } else if bytes.starts_with(&[0xfd, b'7', b'z', b'X', b'Z', 0x00]) {
+        Some(Compression::Xz)
+    } else {
+        None
+    }
+}
+
+// bytes of the skippable frame of zstd with the header, the magic number is 0x184d2a50..0x184d2a5f
+fn get_zstd_skippable_frame_size(bytes: &[u8]) -> Option<usize> {
+    if bytes.len() < 8 || bytes[0] & 0xf0 != 0x50 || bytes[1..4] != [0x2a, 0x4d, 0x18] {
+        return None;
+    }
+    let size = u32::from_le_bytes([bytes[4], bytes[5], bytes[6], bytes[7]]);
+    Some(8 + size as usize)
+}
+
+// decompress all streams of the bytes, e.g. concatenated gzip files
+pub fn decompress(bytes: &[u8], compression: Compression) -> io::Result<Vec<u8>> {
+    let mut decompressed = vec![];
+    match compression {
+        Compression::Gzip => {
+            flate2::read::MultiGzDecoder::new(bytes).read_to_end(&mut decompressed)?;
+        }
+        Compression::Zstd => {
+            let mut input = bytes;
+            while !input.is_empty() {
+                if let Some(size) = get_zstd_skippable_frame_size(input) {
+                    // NOTE: skippable frames are metadata, e.g. the seek table of the seekable format
+                    input = &input[size.min(input.len())..];
+                    continue;
+                }
+                ruzstd::decoding::StreamingDecoder::new(&mut input)
+                    .map_err(io::Error::other)?
+                    .read_to_end(&mut decompressed)?;
+            }
+        }
+        Compression::Bzip2 => {
+            bzip2::read::MultiBzDecoder::new(bytes).read_to_end(&mut decompressed)?;
+        }
+        Compression::Xz => {
+            lzma_rs::xz_decompress(&mut io::BufReader::new(bytes), &mut decompressed)
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string()))?;
+        }
+    }
+    Ok(decompressed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    #[test]
+    fn test_detect() {
+        assert_eq!(detect(&[0x1f, 0x8b, 0x08, 0x00]), Some(Compression::Gzip));
+        assert_eq!(detect(&[0x28, 0xb5, 0x2f, 0xfd, 0x00]), Some(Compression::Zstd));
+        assert_eq!(detect(b"BZh91AY&SY\x00"), Some(Compression::Bzip2));
+        assert_eq!(detect(&[0xfd, b'7', b'z', b'X', b'Z', 0x00]), Some(Compression::Xz));
+        assert_eq!(detect(b"plain text\n"), None);
+        assert_eq!(detect(b""), None);
+    }
+
+    #[test]
+    fn test_detect_text_with_magic_prefix() {
+        assert_eq!(detect(b"BZh is a prefix\n"), None);
+        assert_eq!(detect(b"BZh0AY&SY\n"), None);
+        assert_eq!(detect(&[0x1f, 0x8b]), None);
+    }
+
+    #[test]
+    fn test_decompress_gzip() {
+        let mut encoder = flate2::write::GzEncoder::new(vec![], flate2::Compression::default());
+        encoder.write_all(b"hello\n").unwrap();
+        let bytes = encoder.finish().unwrap();
+        assert_eq!(detect(&bytes), Some(Compression::Gzip));
+        assert_eq!(decompress(&bytes, Compression::Gzip).unwrap(), b"hello\n");
+        // concatenated streams
+        let concatenated = [bytes.clone(), bytes].concat();
+        assert_eq!(decompress(&concatenated, Compression::Gzip).unwrap(), b"hello\nhello\n");
+    }
+
+    #[test]
+    fn test_decompress_bzip2() {
+        let mut encoder = bzip2::write::BzEncoder::new(vec![], bzip2::Compression::default());
+        encoder.write_all(b"hello\n").unwrap();
+        let bytes = encoder.finish().unwrap();
+        assert_eq!(detect(&bytes), Some(Compression::Bzip2));
+        assert_eq!(decompress(&bytes, Compression::Bzip2).unwrap(), b"hello\n");
+
+        let bytes = bzip2::write::BzEncoder::new(vec![], bzip2::Compression::default())
+            .finish()
+            .unwrap();
+        assert_eq!(detect(&bytes), Some(Compression::Bzip2));
+    }
+
+    #[test]
+    fn test_decompress_zstd() {
+        let frame = |text: &[u8]| ruzstd::encoding::compress_to_vec(text, ruzstd::encoding::CompressionLevel::Fastest);
+        let bytes = frame(b"hello\n");
+        assert_eq!(detect(&bytes), Some(Compression::Zstd));
+        assert_eq!(decompress(&bytes, Compression::Zstd).unwrap(), b"hello\n");
+
+        // multiple frames and the skippable frame, e.g. the seek table of the seekable format
+        let skippable = [0x5e, 0x2a, 0x4d, 0x18, 0x03, 0x00, 0x00, 0x00, 0x01, 0x02, 0x03];
+        let bytes = [frame(b"hello\n"), frame(b"world\n"), skippable.to_vec()].concat();
+        assert_eq!(decompress(&bytes, Compression::Zstd).unwrap(), b"hello\nworld\n");
+        let bytes = [skippable.to_vec(), frame(b"hello\n")].concat();
+        assert_eq!(decompress(&bytes, Compression::Zstd).unwrap(), b"hello\n");
+    }
+
+    #[test]
+    fn test_decompress_xz() {
+        let mut bytes = vec![];
+        lzma_rs::xz_compress(&mut &b"hello\n"[..], &mut bytes).unwrap();
+        assert_eq!(detect(&bytes), Some(Compression::Xz));
+        assert_eq!(decompress(&bytes, Compression::Xz).unwrap(), b"hello\n");
+    }
+
+    #[test]
+    fn test_decompress_error() {
+        assert!(decompress(&[0x1f, 0x8b, 0x08, 0x00], Compression::Gzip).is_err());
+    }
+}
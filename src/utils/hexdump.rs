@@ -0,0 +1,139 @@
+use std::fmt::Write;
+
+// bytes of each line in the hex dump view
+pub const LINE_BYTES: usize = 16;
+// columns of the hex bytes, after the offset and two spaces
+const HEX_COLUMNS: usize = LINE_BYTES * 3 + 1;
+// head bytes to detect the binary content
+const BINARY_CHECK_BYTES: usize = 8000;
+
+// NOTE: same as git and grep, the content is binary when a NUL byte is in the head
+pub fn is_binary(bytes: &[u8]) -> bool {
+    bytes[..bytes.len().min(BINARY_CHECK_BYTES)].contains(&0)
+}
+
+// columns of the offset, 8 hex digits and more digits from 4 GiB
+fn offset_width(offset: usize) -> usize {
+    ((usize::BITS - offset.leading_zeros()) as usize).div_ceil(4).max(8)
+}
+
+// column of the first hex byte of the line at the byte offset
+fn hex_start_col(offset: usize) -> usize {
+    offset_width(offset) + 2
+}
+
+// column of the hex byte in the line at the byte offset, the byte index is in the line
+pub fn hex_col(offset: usize, byte_idx: usize) -> usize {
+    hex_start_col(offset) + byte_idx * 3 + if byte_idx >= LINE_BYTES / 2 { 1 } else { 0 }
+}
+
+// lines of the offset, the hex bytes and the ASCII chars, same as `hexdump -C`
+// e.g. 00000000  7f 45 4c 46 02 01 01 00  00 00 00 00 00 00 00 00  |.ELF............|
+// the offset is the byte offset of the bytes in the content, use with the appended bytes
+pub fn dump(bytes: &[u8], offset: usize) -> String {
+    let mut text =
+        String::with_capacity(bytes.len() / LINE_BYTES * (hex_start_col(offset) + HEX_COLUMNS + LINE_BYTES + 4));
+    for (idx, chunk) in bytes.chunks(LINE_BYTES).enumerate() {
+        let line_start = text.len();
+        let line_offset = offset + idx * LINE_BYTES;
+        let _ = write!(text, "{:08x}  ", line_offset);
+        for (i, b) in chunk.iter().enumerate() {
+            let _ = write!(text, "{:02x} ", b);
+            if i == LINE_BYTES / 2 - 1 {
+                text.push(' ');
+            }
+        }
+        // NOTE: align the ASCII column of the last short line
+        let padding = (hex_start_col(line_offset) + HEX_COLUMNS).saturating_sub(text.len() - line_start);
+        text.extend(std::iter::repeat_n(' ', padding));
+        text.push_str(" |");
+        text.extend(chunk.iter().map(|b| {
+            if b.is_ascii_graphic() || *b == b' ' {
+                *b as char
+            } else {
+                '.'
+            }
+        }));
+        text.push_str("|\n");
+    }
+    text
+}
+
+// bytes of the hex dump line
+pub fn parse_line(line: &str) -> Vec<u8> {
+    // NOTE: the offset is longer than 8 digits from 4 GiB
+    let start = line.find(' ').unwrap_or(line.len()) + 2;
+    line.get(start..(start + HEX_COLUMNS).min(line.len()))
+        .unwrap_or("")
+        .split_whitespace()
+        .filter_map(|hex| u8::from_str_radix(hex, 16).ok())
+        .collect()
+}
+
+// bytes of the hex byte pattern, e.g. "7f 45 4c 46" or "7f454c46"
+pub fn parse_hex_pattern(pattern: &str) -> Option<Vec<u8>> {
+    let digits: Vec<char> = pattern.chars().filter(|c| !c.is_whitespace()).collect();
+    if digits.is_empty() || !digits.len().is_multiple_of(2) || !digits.iter().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+    digits
+        .chunks(2)
+        .map(|hex| u8::from_str_radix(&String::from_iter(hex), 16).ok())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_binary() {
+        assert!(is_binary(b"ab\0c"));
+        assert!(!is_binary(b"abc\n"));
+        assert!(!is_binary(b""));
+    }
+
+    #[test]
+    fn test_dump() {
+        let text = dump(b"\x7fELF\x02\x01\x01\x00\x00\x00\x00\x00\x00\x00\x00\x00AB", 0);
+        assert_eq!(
+            text,
+            "00000000  7f 45 4c 46 02 01 01 00  00 00 00 00 00 00 00 00  |.ELF............|\n\
+             00000010  41 42                                             |AB|\n"
+        );
+        assert!(dump(b"A", 0x20).starts_with("00000020  41 "));
+        assert_eq!(dump(b"", 0), "");
+
+        // the offset from 4 GiB is 9 digits, the columns are shifted
+        let text = dump(b"A", 0x1_0000_0000);
+        assert_eq!(text, "100000000  41                                                |A|\n");
+        assert_eq!(parse_line(text.trim_end()), b"A");
+        assert_eq!(&text[hex_col(0x1_0000_0000, 0)..][..2], "41");
+    }
+
+    #[test]
+    fn test_parse_line() {
+        let bytes: Vec<u8> = (0..20).collect();
+        for (line, chunk) in dump(&bytes, 0).lines().zip(bytes.chunks(LINE_BYTES)) {
+            assert_eq!(parse_line(line), chunk);
+        }
+        assert!(parse_line("").is_empty());
+    }
+
+    #[test]
+    fn test_hex_col() {
+        assert_eq!(hex_col(0, 0), 10);
+        assert_eq!(hex_col(0x10, 7), 31);
+        assert_eq!(hex_col(0xffff_fff0, 8), 35);
+        assert_eq!(hex_col(0x1_0000_0000, 0), 11);
+    }
+
+    #[test]
+    fn test_parse_hex_pattern() {
+        assert_eq!(parse_hex_pattern("7f 45 4c 46"), Some(vec![0x7f, 0x45, 0x4c, 0x46]));
+        assert_eq!(parse_hex_pattern("7F454c"), Some(vec![0x7f, 0x45, 0x4c]));
+        assert_eq!(parse_hex_pattern("7f4"), None);
+        assert_eq!(parse_hex_pattern("zz"), None);
+        assert_eq!(parse_hex_pattern(" "), None);
+    }
+}
@@ -1,8 +1,343 @@
-pub fn get_stripped_line_length(line: ropey::RopeSlice) -> usize {
-    let result = if let Some(v) = line.as_str() {
-        v.trim_end().len()
+use std::borrow::Cow;
+
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
+
+use crate::utils;
+
+// grapheme of the line in the display
+#[derive(Clone, Copy, Debug)]
+pub struct Cell {
+    pub char_idx: usize,
+    pub char_len: usize,
+    // display column
+    pub col: usize,
+    pub width: usize,
+}
+
+// length of the line in chars, without the line break
+pub fn get_line_length(line: ropey::RopeSlice) -> usize {
+    let len = line.len_chars();
+    if len > 0 && line.char(len - 1) == '\n' {
+        if len > 1 && line.char(len - 2) == '\r' {
+            return len - 2;
+        }
+        return len - 1;
+    }
+    len
+}
+
+// visible notation of the control char and the invalid byte, same as less. e.g. ^A, <FF>
+pub fn get_char_notation(c: char) -> Option<String> {
+    if let Some(b) = utils::bytes::invalid_byte(c) {
+        return Some(format!("<{:02X}>", b));
+    }
+    match c {
+        '\t' => None,
+        '\x00'..='\x1f' | '\x7f' => Some(format!("^{}", (c as u8 ^ 0x40) as char)),
+        // NOTE: C1 control chars are interpreted by some terminals
+        '\u{80}'..='\u{9f}' => Some(format!("<U+{:04X}>", c as u32)),
+        // NOTE: the format chars (e.g. bidi overrides) are invisible and change the display of the other text.
+        // ZWJ/ZWNJ, the tags and the prepended chars are not here, they are a part of the other grapheme
+        '\u{ad}'
+        | '\u{61c}'
+        | '\u{180e}'
+        | '\u{200b}'
+        | '\u{200e}'..='\u{200f}'
+        | '\u{202a}'..='\u{202e}'
+        | '\u{2060}'..='\u{2064}'
+        | '\u{2066}'..='\u{206f}'
+        | '\u{feff}'
+        | '\u{fff9}'..='\u{fffb}'
+        | '\u{13430}'..='\u{1343f}'
+        | '\u{1bca0}'..='\u{1bca3}'
+        | '\u{1d173}'..='\u{1d17a}'
+        | '\u{e0001}' => Some(format!("<U+{:04X}>", c as u32)),
+        _ => None,
+    }
+}
+
+// graphemes of the line with the display columns, without the line break.
+// the wide chars of East Asian Width take two columns, and the tab is expanded to the next tab stop
+pub fn get_cells(line: ropey::RopeSlice, tab_width: usize) -> Vec<Cell> {
+    let tab_width = tab_width.max(1);
+    let text: Cow<str> = line.slice(..get_line_length(line)).into();
+    let mut cells = vec![];
+    let (mut char_idx, mut col) = (0, 0);
+    for grapheme in text.graphemes(true) {
+        let char_len = grapheme.chars().count();
+        let width = if grapheme == "\t" {
+            tab_width - col % tab_width
+        } else if let Some(notation) = grapheme.chars().next().and_then(get_char_notation) {
+            notation.len()
+        } else {
+            grapheme.width()
+        };
+        cells.push(Cell {
+            char_idx,
+            char_len,
+            col,
+            width,
+        });
+        char_idx += char_len;
+        col += width;
+    }
+    cells
+}
+
+// width of the line in the display columns, without the line break
+pub fn get_line_width(line: ropey::RopeSlice, tab_width: usize) -> usize {
+    get_cells(line, tab_width)
+        .last()
+        .map_or(0, |cell| cell.col + cell.width)
+}
+
+// the line of the printable ASCII chars and the tabs fits in the width, without the grapheme segmentation.
+// false for the other lines even when they fit
+pub fn is_ascii_within(line: ropey::RopeSlice, width: usize, tab_width: usize) -> bool {
+    let tab_width = tab_width.max(1);
+    let mut col = 0;
+    // the CR only before the LF is the line break, the others are shown as ^M
+    let mut is_cr = false;
+    for chunk in line.chunks() {
+        for b in chunk.bytes() {
+            match b {
+                b'\n' => return true,
+                _ if is_cr => return false,
+                b'\r' => is_cr = true,
+                b'\t' => col += tab_width - col % tab_width,
+                b' '..=b'~' => col += 1,
+                _ => return false,
+            }
+            if col > width {
+                return false;
+            }
+        }
+    }
+    !is_cr
+}
+
+// display columns where the cursor can be placed, the first column of each grapheme
+pub fn get_cursor_cols(line: ropey::RopeSlice, tab_width: usize) -> Vec<usize> {
+    get_cells_cursor_cols(&get_cells(line, tab_width))
+}
+
+// display columns where the cursor can be placed in the cells of the line
+pub fn get_cells_cursor_cols(cells: &[Cell]) -> Vec<usize> {
+    let cols: Vec<usize> = cells
+        .iter()
+        .filter(|cell| cell.width > 0)
+        .map(|cell| cell.col)
+        .collect();
+    if cols.is_empty() {
+        vec![0]
     } else {
-        0
-    };
-    result
+        cols
+    }
+}
+
+// first column of the grapheme at the display column, or the last grapheme when the column is over the line
+pub fn get_cursor_col(line: ropey::RopeSlice, col: usize, tab_width: usize) -> usize {
+    let cols = get_cursor_cols(line, tab_width);
+    cols[cols.partition_point(|c| *c <= col).max(1) - 1]
+}
+
+// display column of the char index
+pub fn char_to_col(line: ropey::RopeSlice, char_idx: usize, tab_width: usize) -> usize {
+    let cells = get_cells(line, tab_width);
+    match cells.iter().find(|cell| cell.char_idx + cell.char_len > char_idx) {
+        Some(cell) => cell.col,
+        None => cells.last().map_or(0, |cell| cell.col + cell.width),
+    }
+}
+
+// char index of the grapheme at the display column
+pub fn col_to_char(line: ropey::RopeSlice, col: usize, tab_width: usize) -> usize {
+    let cells = get_cells(line, tab_width);
+    match cells.iter().find(|cell| cell.col + cell.width > col) {
+        Some(cell) => cell.char_idx,
+        None => cells.last().map_or(0, |cell| cell.char_idx + cell.char_len),
+    }
+}
+
+// the leading graphemes of the text in the display width, a wide char is not split
+pub fn truncate_to_width(text: &str, width: usize) -> &str {
+    let mut col = 0;
+    for (idx, grapheme) in text.grapheme_indices(true) {
+        col += grapheme.width();
+        if col > width {
+            return &text[..idx];
+        }
+    }
+    text
+}
+
+// count of the lines, not including the empty line after the last line break
+pub fn get_line_count(lines: &ropey::Rope) -> usize {
+    let count = lines.len_lines();
+    if lines.line(count - 1).len_chars() == 0 {
+        count - 1
+    } else {
+        count
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // a, family emoji of ZWJ sequence, e with combining acute accent, hiragana a, b
+    const TEXT: &str = "a\u{1F468}\u{200D}\u{1F469}\u{200D}\u{1F467}e\u{301}\u{3042}b\n";
+
+    #[test]
+    fn test_get_cells() {
+        let lines = ropey::Rope::from_str(TEXT);
+        let cells: Vec<(usize, usize, usize, usize)> = get_cells(lines.line(0), 8)
+            .iter()
+            .map(|cell| (cell.char_idx, cell.char_len, cell.col, cell.width))
+            .collect();
+        assert_eq!(cells, vec![(0, 1, 0, 1), (1, 5, 1, 2), (6, 2, 3, 1), (8, 1, 4, 2), (9, 1, 6, 1)]);
+        assert_eq!(get_line_width(lines.line(0), 8), 7);
+
+        let lines = ropey::Rope::from_str("\u{4E2D}\r\n\n");
+        assert_eq!(get_cells(lines.line(0), 8).len(), 1);
+        assert!(get_cells(lines.line(1), 8).is_empty());
+    }
+
+    #[test]
+    fn test_char_to_col() {
+        let lines = ropey::Rope::from_str(TEXT);
+        let line = lines.line(0);
+        assert_eq!(char_to_col(line, 0, 8), 0);
+        assert_eq!(char_to_col(line, 1, 8), 1);
+        // inside the ZWJ sequence and the combining grapheme
+        assert_eq!(char_to_col(line, 3, 8), 1);
+        assert_eq!(char_to_col(line, 7, 8), 3);
+        assert_eq!(char_to_col(line, 8, 8), 4);
+        assert_eq!(char_to_col(line, 9, 8), 6);
+        // over the line
+        assert_eq!(char_to_col(line, 10, 8), 7);
+        assert_eq!(char_to_col(line, 100, 8), 7);
+    }
+
+    #[test]
+    fn test_col_to_char() {
+        let lines = ropey::Rope::from_str(TEXT);
+        let line = lines.line(0);
+        assert_eq!(col_to_char(line, 0, 8), 0);
+        assert_eq!(col_to_char(line, 1, 8), 1);
+        // the second column of the wide graphemes
+        assert_eq!(col_to_char(line, 2, 8), 1);
+        assert_eq!(col_to_char(line, 5, 8), 8);
+        assert_eq!(col_to_char(line, 3, 8), 6);
+        assert_eq!(col_to_char(line, 6, 8), 9);
+        // over the line
+        assert_eq!(col_to_char(line, 7, 8), 10);
+        assert_eq!(col_to_char(line, 100, 8), 10);
+    }
+
+    #[test]
+    fn test_get_cursor_col() {
+        let lines = ropey::Rope::from_str(TEXT);
+        let line = lines.line(0);
+        assert_eq!(get_cursor_cols(line, 8), vec![0, 1, 3, 4, 6]);
+        assert_eq!(get_cursor_col(line, 0, 8), 0);
+        // the second column of the wide graphemes
+        assert_eq!(get_cursor_col(line, 2, 8), 1);
+        assert_eq!(get_cursor_col(line, 5, 8), 4);
+        assert_eq!(get_cursor_col(line, 6, 8), 6);
+        assert_eq!(get_cursor_col(line, 100, 8), 6);
+
+        // the zero width grapheme is skipped
+        let lines = ropey::Rope::from_str("\u{301}a\n\n");
+        assert_eq!(get_cursor_cols(lines.line(0), 8), vec![0]);
+        assert_eq!(get_cursor_cols(lines.line(1), 8), vec![0]);
+        assert_eq!(get_cursor_col(lines.line(1), 5, 8), 0);
+    }
+
+    #[test]
+    fn test_tab_expansion() {
+        let widths = |text: &str, tab_width: usize| -> Vec<usize> {
+            let lines = ropey::Rope::from_str(text);
+            get_cells(lines.line(0), tab_width)
+                .iter()
+                .map(|cell| cell.width)
+                .collect()
+        };
+        // the tab is expanded to the next tab stop
+        assert_eq!(widths("\tb", 8), vec![8, 1]);
+        assert_eq!(widths("ab\tc", 8), vec![1, 1, 6, 1]);
+        assert_eq!(widths("abcdefgh\tc", 8)[8], 8);
+        assert_eq!(widths("a\t\tb", 8), vec![1, 7, 8, 1]);
+        // the tab after the wide chars
+        assert_eq!(widths("\u{3042}\tb", 8), vec![2, 6, 1]);
+        assert_eq!(widths("\u{3042}\u{3042}\u{3042}\u{3042}\tb", 8)[4], 8);
+        // -x4
+        assert_eq!(widths("a\tb", 4), vec![1, 3, 1]);
+        assert_eq!(widths("\u{3042}\u{3042}\tb", 4), vec![2, 2, 4, 1]);
+        assert_eq!(widths("\u{3042}a\tb", 4), vec![2, 1, 1, 1]);
+
+        let lines = ropey::Rope::from_str("\u{3042}\tb\n");
+        assert_eq!(char_to_col(lines.line(0), 2, 4), 4);
+        assert_eq!(col_to_char(lines.line(0), 3, 4), 1);
+        assert_eq!(get_cursor_col(lines.line(0), 3, 4), 2);
+    }
+
+    #[test]
+    fn test_truncate_to_width() {
+        assert_eq!(truncate_to_width("abc", 2), "ab");
+        assert_eq!(truncate_to_width("abc", 5), "abc");
+        // the wide char over the width is dropped
+        assert_eq!(truncate_to_width("a\u{65E5}\u{672C}", 4), "a\u{65E5}");
+        assert_eq!(truncate_to_width("a\u{65E5}\u{672C}", 2), "a");
+        assert_eq!(truncate_to_width("e\u{301}x", 1), "e\u{301}");
+        assert_eq!(truncate_to_width("abc", 0), "");
+    }
+
+    #[test]
+    fn test_is_ascii_within() {
+        let lines = ropey::Rope::from_str("abcd\nabcd\r\na\tb\na\u{3042}\na\x1bb\n\nab\rc");
+        assert!(is_ascii_within(lines.line(0), 4, 8));
+        assert!(!is_ascii_within(lines.line(0), 3, 8));
+        assert!(is_ascii_within(lines.line(1), 4, 8));
+        assert!(is_ascii_within(lines.line(2), 9, 8));
+        assert!(!is_ascii_within(lines.line(2), 8, 8));
+        assert!(is_ascii_within(lines.line(2), 5, 4));
+        assert!(!is_ascii_within(lines.line(3), 80, 8));
+        assert!(!is_ascii_within(lines.line(4), 80, 8));
+        assert!(is_ascii_within(lines.line(5), 0, 8));
+        // the CR not before the LF
+        assert!(!is_ascii_within(lines.line(6), 80, 8));
+        assert!(!is_ascii_within(ropey::Rope::from_str("a\rb\n").line(0), 80, 8));
+    }
+
+    #[test]
+    fn test_get_char_notation() {
+        assert_eq!(get_char_notation('\x01').as_deref(), Some("^A"));
+        assert_eq!(get_char_notation('\x1b').as_deref(), Some("^["));
+        assert_eq!(get_char_notation('\x7f').as_deref(), Some("^?"));
+        assert_eq!(get_char_notation('\u{85}').as_deref(), Some("<U+0085>"));
+        assert_eq!(
+            get_char_notation(utils::bytes::decode_lossy(b"\xff").chars().next().unwrap()).as_deref(),
+            Some("<FF>")
+        );
+        // format chars
+        assert_eq!(get_char_notation('\u{202E}').as_deref(), Some("<U+202E>"));
+        assert_eq!(get_char_notation('\u{2066}').as_deref(), Some("<U+2066>"));
+        assert_eq!(get_char_notation('\u{200B}').as_deref(), Some("<U+200B>"));
+        assert_eq!(get_char_notation('\u{FEFF}').as_deref(), Some("<U+FEFF>"));
+        assert_eq!(get_char_notation('\u{E0001}').as_deref(), Some("<U+E0001>"));
+        // printed as is
+        assert_eq!(get_char_notation('\t'), None);
+        assert_eq!(get_char_notation('a'), None);
+        assert_eq!(get_char_notation('\u{3042}'), None);
+        assert_eq!(get_char_notation('\u{200D}'), None);
+        assert_eq!(get_char_notation('\u{301}'), None);
+
+        // the notation takes the columns, the ZWJ of emoji is kept in the grapheme
+        let lines = ropey::Rope::from_str("a\u{202E}b\u{1F468}\u{200D}\u{1F469}\n");
+        let cells = get_cells(lines.line(0), 8);
+        assert_eq!((cells[1].col, cells[1].width), (1, 8));
+        assert_eq!((cells[3].char_len, cells[3].col), (3, 10));
+    }
 }
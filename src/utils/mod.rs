@@ -1 +1,5 @@
+pub mod ansi;
+pub mod bytes;
+pub mod compress;
+pub mod hexdump;
 pub mod line;